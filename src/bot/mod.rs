@@ -0,0 +1,269 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::bot::vectors::WordVectors;
+use crate::client::{Client, ClientError};
+use crate::game::chat::Channel;
+use crate::game::clue::{Clue, MAX_COUNT};
+use crate::game::{Color, Team};
+use crate::protocol::{Card, Event};
+
+pub mod vectors;
+
+/// A clue must be this much closer to a card of its team than to the assassin.
+const ASSASSIN_MARGIN: f32 = 0.1;
+/// Bystanders hurt less than opponent cards, so they may come this much closer.
+const BYSTANDER_ALLOWANCE: f32 = 0.05;
+/// Cards less similar to a clue than this are never counted for it.
+const MIN_SIMILARITY: f32 = 0.25;
+
+/// Gives clues by comparing word vectors of the candidates with the cards on the board.
+pub struct Spymaster {
+    model: WordVectors,
+    /// Most frequent words of the model considered as clues.
+    candidates: usize,
+}
+
+/// How well a candidate works as a clue, compared by the number of cards first.
+#[derive(Debug, PartialEq, PartialOrd)]
+struct Score {
+    cards: usize,
+    /// Distance between the weakest counted card and the closest card of another team.
+    margin: f32,
+}
+
+impl Spymaster {
+    pub fn new(model: WordVectors, candidates: usize) -> Self {
+        Self {
+            model,
+            candidates,
+        }
+    }
+
+    /// Proposes a clue for `team` from the key card, leaving out the `revealed` cards.
+    /// Without a candidate that fits any card better than the others, the best single card gets a clue for one.
+    pub fn propose(&self, team: &Color, cards: &[Card], revealed: &HashSet<String>) -> Option<Clue> {
+        let hidden: Vec<&Card> = cards.iter().filter(|card| !revealed.contains(&card.word)).collect();
+        let own = Team::Player(team.clone());
+        let mut best: Option<(Score, &String)> = None;
+        for candidate in self.model.words().iter().take(self.candidates) {
+            if !candidate.chars().all(char::is_alphabetic) {
+                continue;
+            }
+            let clue = Clue {
+                word: candidate.clone(),
+                count: 1,
+            };
+            if clue.check_hidden(hidden.iter().map(|card| card.word.as_str())).is_err() {
+                continue;
+            }
+            if let Some(score) = self.score(candidate, &own, &hidden) {
+                if best.as_ref().map(|(best, _)| score > *best).unwrap_or(true) {
+                    best = Some((score, candidate));
+                }
+            }
+        }
+        best.map(|(score, word)| Clue {
+            word: word.clone(),
+            count: score.cards.max(1).min(MAX_COUNT),
+        })
+    }
+
+    fn score(&self, candidate: &str, own: &Team, hidden: &[&Card]) -> Option<Score> {
+        let danger = hidden.iter()
+            .filter(|card| card.team.ne(own))
+            .filter_map(|card| {
+                let similarity = self.model.similarity(candidate, &card.word)?;
+                Some(match card.team {
+                    Team::Death => similarity + ASSASSIN_MARGIN,
+                    Team::None => similarity - BYSTANDER_ALLOWANCE,
+                    Team::Player(_) => similarity,
+                })
+            })
+            .fold(MIN_SIMILARITY, f32::max);
+        let mut similarities: Vec<f32> = hidden.iter()
+            .filter(|card| card.team.eq(own))
+            .filter_map(|card| self.model.similarity(candidate, &card.word))
+            .collect();
+        if similarities.is_empty() {
+            return None;
+        }
+        // `WordVectors::read` rejects NaN, so the fallback never applies
+        similarities.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let cards = similarities.iter().filter(|similarity| **similarity > danger).count();
+        let weakest = similarities[cards.max(1) - 1];
+        Some(Score {
+            cards,
+            margin: weakest - danger,
+        })
+    }
+}
+
+/// Follows what the bot needs to know about the game.
+#[derive(Default)]
+struct View {
+    cards: Vec<Card>,
+    revealed: HashSet<String>,
+    turn: Option<Color>,
+    finished: bool,
+    clue: bool,
+    /// Whether a clue was sent this turn, so it is sent once even before the server confirms it.
+    /// Cleared when the server answers with an error, so a rejected clue is followed by another try.
+    sent: bool,
+}
+
+impl View {
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Spy(cards) => self.cards = cards,
+            Event::State(state) => {
+                self.revealed = state.revealed.into_iter().map(|card| card.word).collect();
+                self.set_turn(state.team);
+                self.finished = state.winner.is_some();
+                self.clue = state.clue.is_some();
+            }
+            Event::Reveal(card) => {
+                self.revealed.insert(card.word);
+            }
+            Event::Turn(team) => self.set_turn(team),
+            Event::Win(_) => self.finished = true,
+            Event::Reload { .. } => {
+                self.cards.clear();
+                self.revealed.clear();
+                self.sent = false;
+            }
+            Event::Error(message) => {
+                warn!("Server: {}", message);
+                self.sent = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn set_turn(&mut self, team: Color) {
+        if self.turn.as_ref() != Some(&team) {
+            self.sent = false;
+        }
+        self.turn = Some(team);
+    }
+
+    /// Whether `team` waits for a clue that was not sent yet.
+    fn needs_clue(&self, team: &Color) -> bool {
+        !self.finished && !self.clue && !self.sent && !self.cards.is_empty() && self.turn.as_ref() == Some(team)
+    }
+}
+
+/// Sits at the table as the spymaster of `team` and gives a clue whenever it is the team's turn.
+/// Without a clue to give it says so and passes the turn, so the table is not left waiting.
+pub fn run(mut client: Client, spymaster: &Spymaster, name: &str, team: Color, timeout: Duration) -> Result<(), ClientError> {
+    client.join(name, team.clone(), true, false)?;
+    client.spy()?;
+    let mut view = View::default();
+    loop {
+        let msg = match client.recv(timeout) {
+            Ok(msg) => msg,
+            Err(ClientError::Timeout) => {
+                client.refresh()?;
+                continue;
+            }
+            Err(ClientError::Closed) => {
+                client.reconnect(timeout)?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for event in msg.events {
+            if let Event::Reload { .. } = event {
+                client.refresh()?;
+                client.spy()?;
+            }
+            view.apply(event);
+        }
+        if view.needs_clue(&team) {
+            view.sent = true;
+            match spymaster.propose(&team, &view.cards, &view.revealed) {
+                Some(clue) => {
+                    info!("Giving clue {} for {}", clue, team);
+                    client.clue(clue)?;
+                }
+                None => {
+                    warn!("No clue for {}, the model knows none of the cards", team);
+                    client.chat(Channel::Global, "I know none of our cards, passing the turn.")?;
+                    client.skip()?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::bot::vectors::WordVectors;
+    use crate::bot::{Spymaster, View};
+    use crate::game::clue::Clue;
+    use crate::game::{Color, Team};
+    use crate::protocol::{Card, Event};
+
+    const MODEL: &str = "\
+ocean 1 0.1 0
+flame 0 0.2 1
+sea 0.9 0.3 0
+wave 0.8 0 0.2
+river 0.6 0.4 0.5
+fire 0 0 1
+desk 0 1 0
+";
+
+    fn spymaster() -> Spymaster {
+        Spymaster::new(WordVectors::read(MODEL.as_bytes()).unwrap(), 100)
+    }
+
+    fn card(word: &str, team: Team) -> Card {
+        Card { word: word.into(), team }
+    }
+
+    #[test]
+    fn clue_relates_to_own_cards() {
+        let cards = vec![
+            card("sea", Team::Player(Color::Red)),
+            card("wave", Team::Player(Color::Red)),
+            card("fire", Team::Death),
+            card("desk", Team::Player(Color::Blue)),
+        ];
+        let clue = spymaster().propose(&Color::Red, &cards, &HashSet::new());
+        assert_eq!(Some(Clue { word: "ocean".into(), count: 2 }), clue);
+    }
+
+    #[test]
+    fn revealed_cards_are_ignored() {
+        let cards = vec![
+            card("sea", Team::Player(Color::Blue)),
+            card("wave", Team::Player(Color::Blue)),
+            card("fire", Team::Player(Color::Red)),
+            card("desk", Team::Death),
+        ];
+        let revealed: HashSet<String> = vec!["sea".to_string(), "wave".into()].into_iter().collect();
+        let clue = spymaster().propose(&Color::Red, &cards, &revealed);
+        assert_eq!(Some(Clue { word: "flame".into(), count: 1 }), clue);
+    }
+
+    #[test]
+    fn no_clue_without_known_cards() {
+        let cards = vec![card("castle", Team::Player(Color::Red))];
+        assert_eq!(None, spymaster().propose(&Color::Red, &cards, &HashSet::new()));
+    }
+
+    #[test]
+    fn rejected_clue_is_given_again() {
+        let mut view = View::default();
+        view.cards.push(card("sea", Team::Player(Color::Red)));
+        view.apply(Event::Turn(Color::Red));
+        view.sent = true;
+        assert!(!view.needs_clue(&Color::Red));
+        view.apply(Event::Error("Clues may not name a card on the board.".into()));
+        assert!(view.needs_clue(&Color::Red));
+    }
+}
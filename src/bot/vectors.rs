@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use serde::export::Formatter;
+
+#[derive(Debug)]
+pub enum ModelError {
+    NotConfigured,
+    Io(io::Error),
+    /// A line that is not a word followed by as many finite numbers as the lines before.
+    Format(usize),
+    Empty,
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::NotConfigured => write!(f, "No word vectors configured, set bot.models_dir"),
+            ModelError::Io(e) => write!(f, "Could not read word vectors: {}", e),
+            ModelError::Format(line) => write!(f, "Invalid word vector in line {}", line),
+            ModelError::Empty => write!(f, "The word vector file is empty"),
+        }
+    }
+}
+
+impl From<io::Error> for ModelError {
+    fn from(e: io::Error) -> ModelError {
+        ModelError::Io(e)
+    }
+}
+
+/// Word vectors in the text format of GloVe and word2vec: a word per line followed by its components,
+/// most frequent words first. Words are looked up in lower case.
+pub struct WordVectors {
+    words: Vec<String>,
+    index: HashMap<String, usize>,
+    /// Scaled to unit length, so the dot product is the cosine similarity.
+    vectors: Vec<Vec<f32>>,
+}
+
+impl WordVectors {
+    /// Loads `<dir>/<language>.txt`.
+    pub fn load(dir: &str, language: &str) -> Result<Self, ModelError> {
+        if dir.is_empty() {
+            return Err(ModelError::NotConfigured);
+        }
+        let file = File::open(Path::new(dir).join(format!("{}.txt", language)))?;
+        WordVectors::read(BufReader::new(file))
+    }
+
+    /// Reads vectors, skipping the `count dimensions` header word2vec files start with.
+    pub fn read<R: BufRead>(input: R) -> Result<Self, ModelError> {
+        let mut model = WordVectors {
+            words: vec![],
+            index: HashMap::new(),
+            vectors: vec![],
+        };
+        for (number, line) in input.lines().enumerate() {
+            let line = line?;
+            let mut parts = line.split_whitespace();
+            let word = match parts.next() {
+                Some(word) => word.to_lowercase(),
+                None => continue,
+            };
+            let vector = parts.map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| ModelError::Format(number + 1))?;
+            if number == 0 && vector.len() == 1 {
+                continue;
+            }
+            let dimensions = model.vectors.first().map(Vec::len).unwrap_or_else(|| vector.len());
+            if vector.is_empty() || vector.len() != dimensions || !vector.iter().all(|x| x.is_finite()) {
+                return Err(ModelError::Format(number + 1));
+            }
+            if model.index.contains_key(&word) {
+                continue;
+            }
+            model.index.insert(word.clone(), model.words.len());
+            model.words.push(word);
+            model.vectors.push(unit(vector));
+        }
+        if model.words.is_empty() {
+            return Err(ModelError::Empty);
+        }
+        Ok(model)
+    }
+
+    /// All words, most frequent first.
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Cosine similarity of two words, `None` if either is unknown.
+    pub fn similarity(&self, a: &str, b: &str) -> Option<f32> {
+        let a = &self.vectors[*self.index.get(&a.to_lowercase())?];
+        let b = &self.vectors[*self.index.get(&b.to_lowercase())?];
+        Some(a.iter().zip(b).map(|(x, y)| x * y).sum())
+    }
+}
+
+fn unit(vector: Vec<f32>) -> Vec<f32> {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length == 0.0 {
+        vector
+    } else {
+        vector.into_iter().map(|x| x / length).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::vectors::{ModelError, WordVectors};
+
+    #[test]
+    fn reads_word2vec_and_glove_files() {
+        let model = WordVectors::read("3 2\nSea 1 0\nwave 2 2\nsea 0 1\n".as_bytes()).unwrap();
+        assert_eq!(vec!["sea".to_string(), "wave".into()], model.words());
+        assert_eq!(Some(1.0), model.similarity("sea", "SEA"));
+        assert!((model.similarity("sea", "wave").unwrap() - 0.5f32.sqrt()).abs() < 1e-6);
+        assert_eq!(None, model.similarity("sea", "fire"));
+    }
+
+    #[test_case("sea 1 0\nwave 1\n" => 2 ; "dimensions differ")]
+    #[test_case("sea 1 zero\n" => 1 ; "not a number")]
+    #[test_case("sea 1 0\nwave\n" => 2 ; "no vector")]
+    #[test_case("sea nan 0\n" => 1 ; "not finite")]
+    #[test_case("sea 1 0\nwave inf 1\n" => 2 ; "infinite")]
+    fn invalid_lines(text: &str) -> usize {
+        match WordVectors::read(text.as_bytes()) {
+            Err(ModelError::Format(line)) => line,
+            _ => 0,
+        }
+    }
+}
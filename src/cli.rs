@@ -0,0 +1,296 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::bot::Spymaster;
+use crate::bot::vectors::WordVectors;
+use crate::client::{Client, ClientError};
+use crate::conf::{CacheBackend, Settings};
+use crate::game::cache::FileGameCache;
+use crate::game::{Color, Game};
+use crate::game::options::GameOptions;
+use crate::print::ColoredDesc;
+use crate::protocol::codec::Encoding;
+use crate::protocol::Role;
+use crate::res::validate::validate;
+use crate::tui::{Seat, TuiError};
+
+pub fn app() -> App<'static, 'static> {
+    App::new("codenamer")
+        .version(crate::VERSION)
+        .about("Codenames server and administration tools")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::with_name("print-config")
+            .long("print-config")
+            .help("Prints the effective configuration and exits"))
+        .subcommand(SubCommand::with_name("serve")
+            .about("Starts the web and socket servers (default)"))
+        .subcommand(SubCommand::with_name("words")
+            .about("Inspects word packs")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("validate")
+                .about("Checks all word packs in a directory")
+                .arg(Arg::with_name("dir").required(true)))
+            .subcommand(SubCommand::with_name("stats")
+                .about("Shows statistics for every loaded language")))
+        .subcommand(SubCommand::with_name("games")
+            .about("Inspects games in the persistent cache")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("Lists all stored games"))
+            .subcommand(SubCommand::with_name("export")
+                .about("Prints a stored game as JSON")
+                .arg(Arg::with_name("name").required(true))))
+        .subcommand(SubCommand::with_name("board")
+            .about("Prints a board with its key card")
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for the random number generator, random if omitted"))
+            .arg(Arg::with_name("language")
+                .long("language")
+                .takes_value(true)
+                .default_value("english")))
+        .subcommand(SubCommand::with_name("play")
+            .about("Plays a game on this computer, passing it between the teams")
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for the random number generator, random if omitted"))
+            .arg(Arg::with_name("language")
+                .long("language")
+                .takes_value(true)
+                .default_value("english")))
+        .subcommand(SubCommand::with_name("bot")
+            .about("Joins a game on a running server as a spymaster that gives clues from word vectors")
+            .arg(Arg::with_name("game").required(true))
+            .arg(Arg::with_name("team")
+                .long("team")
+                .takes_value(true)
+                .possible_values(&["red", "blue"])
+                .required(true))
+            .arg(Arg::with_name("language")
+                .long("language")
+                .takes_value(true)
+                .default_value("english")
+                .help("Language of the game, selects the word vectors in bot.models_dir"))
+            .arg(Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .default_value("Bot"))
+            .arg(Arg::with_name("ident")
+                .long("ident")
+                .takes_value(true)
+                .required(true)
+                .help("Ident of the game, as in its link or in games export"))
+            .arg(Arg::with_name("url")
+                .long("url")
+                .takes_value(true)
+                .help("Socket server to connect to, socket.url if omitted")))
+        .subcommand(SubCommand::with_name("watch")
+            .about("Follows a game on a running server and prints its events")
+            .arg(Arg::with_name("game").required(true))
+            .arg(Arg::with_name("ident")
+                .long("ident")
+                .takes_value(true)
+                .required(true)
+                .help("Watch ident of the game, the end of its spectator link"))
+            .arg(Arg::with_name("url")
+                .long("url")
+                .takes_value(true)
+                .help("Socket server to connect to, socket.url if omitted"))
+            .arg(Arg::with_name("msgpack")
+                .long("msgpack")
+                .help("Uses MessagePack instead of JSON")))
+        .subcommand(SubCommand::with_name("tui")
+            .about("Plays a game on a running server in the terminal")
+            .arg(Arg::with_name("game").required(true))
+            .arg(Arg::with_name("ident")
+                .long("ident")
+                .takes_value(true)
+                .required(true)
+                .help("Ident of the game, or its watch ident when only watching"))
+            .arg(Arg::with_name("url")
+                .long("url")
+                .takes_value(true)
+                .help("Socket server to connect to, socket.url if omitted"))
+            .arg(Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("Joins a team under this name, only watches if omitted"))
+            .arg(Arg::with_name("team")
+                .long("team")
+                .takes_value(true)
+                .possible_values(&["red", "blue"])
+                .default_value("red"))
+            .arg(Arg::with_name("spymaster")
+                .long("spymaster")
+                .help("Joins as spymaster and shows the key card"))
+            .arg(Arg::with_name("captain")
+                .long("captain")
+                .help("Joins as captain, whose vote decides in voting games")))
+}
+
+/// Runs an administration subcommand and returns the process exit code.
+pub fn run(command: &str, args: &ArgMatches, settings: &Settings) -> i32 {
+    let result = match (command, args.subcommand()) {
+        ("words", ("validate", Some(args))) => words_validate(args.value_of("dir").unwrap()),
+        ("words", ("stats", _)) => words_stats(),
+        ("games", ("list", _)) => games_list(settings),
+        ("games", ("export", Some(args))) => games_export(settings, args.value_of("name").unwrap()),
+        ("board", _) => board(args),
+        ("play", _) => play(args),
+        ("bot", _) => bot(args, settings),
+        ("watch", _) => watch(args, settings).map_err(|e| e.to_string()),
+        ("tui", _) => tui(args, settings).map_err(|e| e.to_string()),
+        _ => Err(format!("Unknown command: {}", command)),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn words_validate(dir: &str) -> Result<(), String> {
+    let packs = crate::res::words::load_dir(Path::new(dir)).map_err(|e| format!("{:?}", e))?;
+    let mut languages: Vec<&String> = packs.keys().collect();
+    languages.sort();
+    let mut errors = 0;
+    for language in languages {
+        let report = validate(&crate::res::words::plain(&packs[language]));
+        println!("{}: {}", language, report.stats);
+        println!("  {} errors, {} warnings", report.errors(), report.warnings());
+        for issue in &report.issues {
+            println!("  {} {}", if issue.is_error() { "error:" } else { "warning:" }, issue);
+        }
+        errors += report.errors();
+    }
+    if errors == 0 {
+        Ok(())
+    } else {
+        Err(format!("Word packs in {} have {} errors", dir, errors))
+    }
+}
+
+fn words_stats() -> Result<(), String> {
+    let mut languages = crate::res::words::languages();
+    languages.sort();
+    for language in languages {
+        let words = crate::res::words::words(&language).map_err(|e| format!("{:?}", e))?;
+        let report = validate(&words);
+        println!("{}: {}", language, report.stats);
+        println!("  {} warnings", report.warnings());
+        let tags = crate::res::words::tags(&language).map_err(|e| format!("{:?}", e))?;
+        for (tag, count) in tags {
+            println!("  {}: {} words", tag, count);
+        }
+    }
+    Ok(())
+}
+
+fn stored_games(settings: &Settings) -> Result<Vec<Game>, String> {
+    if settings.cache.backend != CacheBackend::File {
+        return Err("Game commands need a persistent cache, set cache.backend = \"file\"".into());
+    }
+    FileGameCache::read_games(Path::new(&settings.cache.dir)).map_err(|e| format!("{:?}", e))
+}
+
+fn games_list(settings: &Settings) -> Result<(), String> {
+    let mut games = stored_games(settings)?;
+    games.sort_by(|a, b| a.name.cmp(&b.name));
+    let now = SystemTime::now();
+    for game in games {
+        let age = now.duration_since(game.created).map(|d| d.as_secs() / 60).unwrap_or(0);
+        let status = match &game.winner {
+            Some(winner) => format!("{} won", winner),
+            None => format!("{} to play", game.turn),
+        };
+        println!("{}\t{}\t{}\t{} min", game.name, game.options.words.language, status, age);
+    }
+    Ok(())
+}
+
+fn games_export(settings: &Settings, name: &str) -> Result<(), String> {
+    let game = stored_games(settings)?
+        .into_iter()
+        .find(|game| game.name.eq(name))
+        .ok_or_else(|| format!("No stored game named {}", name))?;
+    let json = serde_json::to_string_pretty(&game).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// A game built from the `seed` and `language` arguments.
+fn seeded_game(args: &ArgMatches) -> Result<Game, String> {
+    let seed = match args.value_of("seed") {
+        Some(seed) => seed.parse::<u64>().map_err(|_| format!("Invalid seed: {}", seed))?,
+        None => rand::random(),
+    };
+    let language = args.value_of("language").unwrap();
+    let mut rng = StdRng::seed_from_u64(seed);
+    Game::with_rng(format!("seed-{}", seed), GameOptions::language(language), &mut rng)
+        .map_err(|e| e.to_string())
+}
+
+fn board(args: &ArgMatches) -> Result<(), String> {
+    println!("{}", seeded_game(args)?.desc_colored());
+    Ok(())
+}
+
+fn play(args: &ArgMatches) -> Result<(), String> {
+    let mut game = seeded_game(args)?;
+    let stdin = std::io::stdin();
+    crate::play::play(&mut game, &mut stdin.lock(), &mut std::io::stdout()).map_err(|e| e.to_string())
+}
+
+fn watch(args: &ArgMatches, settings: &Settings) -> Result<(), ClientError> {
+    let timeout = Duration::from_secs(10);
+    let url = args.value_of("url").unwrap_or(&settings.socket.url);
+    let encoding = if args.is_present("msgpack") { Encoding::MessagePack } else { Encoding::Json };
+    let mut client = Client::connect(url, args.value_of("game").unwrap(), args.value_of("ident").unwrap(), Role::Spectator, encoding, timeout)?;
+    loop {
+        match client.recv(settings.socket.ping_interval) {
+            Ok(msg) => {
+                for event in msg.events {
+                    println!("{:?}", event);
+                }
+            }
+            Err(ClientError::Timeout) => client.refresh()?,
+            Err(ClientError::Closed) => client.reconnect(timeout)?,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn tui(args: &ArgMatches, settings: &Settings) -> Result<(), TuiError> {
+    let timeout = Duration::from_secs(10);
+    let url = args.value_of("url").unwrap_or(&settings.socket.url);
+    let seat = args.value_of("name").map(|name| Seat {
+        name: name.to_string(),
+        team: if args.value_of("team") == Some("blue") { Color::Blue } else { Color::Red },
+        spymaster: args.is_present("spymaster"),
+        captain: args.is_present("captain"),
+    });
+    let role = if seat.is_some() { Role::Player } else { Role::Spectator };
+    let client = Client::connect(url, args.value_of("game").unwrap(), args.value_of("ident").unwrap(), role, Encoding::Json, timeout)?;
+    crate::tui::run(client, seat, timeout)
+}
+
+fn bot(args: &ArgMatches, settings: &Settings) -> Result<(), String> {
+    let timeout = Duration::from_secs(10);
+    let language = args.value_of("language").unwrap();
+    let model = WordVectors::load(&settings.bot.models_dir, language).map_err(|e| e.to_string())?;
+    let spymaster = Spymaster::new(model, settings.bot.candidates);
+    let url = args.value_of("url").unwrap_or(&settings.socket.url);
+    let team = if args.value_of("team") == Some("blue") { Color::Blue } else { Color::Red };
+    let client = Client::connect(url, args.value_of("game").unwrap(), args.value_of("ident").unwrap(), Role::Player, Encoding::Json, timeout)
+        .map_err(|e| e.to_string())?;
+    crate::bot::run(client, &spymaster, args.value_of("name").unwrap(), team, settings.socket.ping_interval)
+        .map_err(|e| e.to_string())
+}
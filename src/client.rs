@@ -0,0 +1,414 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread::{spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::export::Formatter;
+use serde_json::Value;
+use ws::{Builder, CloseCode, Handler, Handshake, Message, Request, Sender};
+
+use crate::game::chat::Channel;
+use crate::game::clue::Clue;
+use crate::game::Color;
+use crate::protocol::codec::Encoding;
+use crate::protocol::{Chat, Event, Join, Msg, MsgParseError, Reset, Reveal, Role, ServerMsg, Step, Vote};
+
+#[derive(Debug)]
+pub enum ClientError {
+    Socket(ws::Error),
+    Protocol(MsgParseError),
+    Url(url::ParseError),
+    /// Nothing arrived in time.
+    Timeout,
+    Closed,
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Socket(e) => write!(f, "socket error: {}", e),
+            ClientError::Protocol(e) => write!(f, "unexpected message from server: {}", e),
+            ClientError::Url(e) => write!(f, "invalid socket url: {}", e),
+            ClientError::Timeout => write!(f, "the server did not answer in time"),
+            ClientError::Closed => write!(f, "the connection is closed"),
+        }
+    }
+}
+
+impl From<ws::Error> for ClientError {
+    fn from(e: ws::Error) -> ClientError {
+        ClientError::Socket(e)
+    }
+}
+
+impl From<MsgParseError> for ClientError {
+    fn from(e: MsgParseError) -> ClientError {
+        ClientError::Protocol(e)
+    }
+}
+
+/// What the connection thread passes on.
+enum Incoming {
+    Open(Sender),
+    Message(ServerMsg),
+    Invalid(MsgParseError),
+    Failed(ws::Error),
+    Closed,
+}
+
+struct Inbox {
+    out: Sender,
+    encoding: Encoding,
+    incoming: std::sync::mpsc::Sender<Incoming>,
+}
+
+impl Inbox {
+    fn pass(&self, incoming: Incoming) -> ws::Result<()> {
+        if self.incoming.send(incoming).is_err() {
+            // the client is gone
+            return self.out.close(CloseCode::Normal);
+        }
+        Ok(())
+    }
+}
+
+impl Handler for Inbox {
+    fn build_request(&mut self, url: &url::Url) -> ws::Result<Request> {
+        let mut request = Request::from_url(url)?;
+        if let Some(protocol) = self.encoding.protocol() {
+            request.add_protocol(protocol);
+        }
+        Ok(request)
+    }
+
+    fn on_open(&mut self, _shake: Handshake) -> ws::Result<()> {
+        self.pass(Incoming::Open(self.out.clone()))
+    }
+
+    fn on_message(&mut self, message: Message) -> ws::Result<()> {
+        self.pass(match ServerMsg::try_from(message) {
+            Ok(msg) => Incoming::Message(msg),
+            Err(e) => Incoming::Invalid(e),
+        })
+    }
+
+    fn on_close(&mut self, _code: CloseCode, _reason: &str) {
+        let _ = self.incoming.send(Incoming::Closed);
+    }
+
+    fn on_error(&mut self, e: ws::Error) {
+        let _ = self.incoming.send(Incoming::Failed(e));
+    }
+}
+
+/// A connection to the socket server following one game, for bots, terminal clients and tests.
+/// Keeps track of the session and the last event seen, so it can resume after losing the connection,
+/// and of the game's ident, which changes when this client resets the game.
+pub struct Client {
+    url: String,
+    game: String,
+    role: Role,
+    encoding: Encoding,
+    out: Sender,
+    incoming: Receiver<Incoming>,
+    thread: Option<JoinHandle<()>>,
+    ident: String,
+    /// Whether a reset was sent whose reload with the next ident has not arrived yet.
+    resetting: bool,
+    session: Option<String>,
+    seq: Option<u64>,
+    /// Messages that arrived while waiting for the session.
+    pending: VecDeque<ServerMsg>,
+}
+
+impl Client {
+    /// Connects to the socket server at `url` and enters `game`, waiting up to `timeout` for the server to confirm.
+    /// `ident` is the game's ident to play as `Role::Player`, or its watch ident to follow it as `Role::Spectator`.
+    pub fn connect(url: &str, game: &str, ident: &str, role: Role, encoding: Encoding, timeout: Duration) -> Result<Client, ClientError> {
+        let (out, incoming, thread) = open(url, encoding, timeout)?;
+        let mut client = Client {
+            url: url.to_string(),
+            game: game.to_string(),
+            role,
+            encoding,
+            out,
+            incoming,
+            thread: Some(thread),
+            ident: ident.to_string(),
+            resetting: false,
+            session: None,
+            seq: None,
+            pending: VecDeque::new(),
+        };
+        client.enter(timeout)?;
+        Ok(client)
+    }
+
+    /// Opens a new connection and resumes the session, receiving the events missed in between.
+    pub fn reconnect(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        self.shutdown();
+        let (out, incoming, thread) = open(&self.url, self.encoding, timeout)?;
+        self.out = out;
+        self.incoming = incoming;
+        self.thread = Some(thread);
+        self.enter(timeout)
+    }
+
+    fn enter(&mut self, timeout: Duration) -> Result<(), ClientError> {
+        self.send(vec![])?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let msg = self.receive(left)?;
+            let confirmed = msg.events.iter().any(|event| match event {
+                Event::Session { .. } => true,
+                _ => false,
+            });
+            self.pending.push_back(msg);
+            if confirmed {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn game(&self) -> &str {
+        &self.game
+    }
+
+    pub fn ident(&self) -> &str {
+        &self.ident
+    }
+
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_ref().map(String::as_str)
+    }
+
+    pub fn send(&mut self, steps: Vec<Step>) -> Result<(), ClientError> {
+        let msg: Value = Msg {
+            game: self.game.clone(),
+            ident: self.ident.clone(),
+            role: self.role,
+            session: self.session.clone(),
+            seq: self.seq,
+            steps,
+        }.into();
+        self.out.send(self.encoding.encode(&msg))?;
+        Ok(())
+    }
+
+    pub fn join(&mut self, name: &str, team: Color, spymaster: bool, captain: bool) -> Result<(), ClientError> {
+        self.send(vec![Step::Join(Join {
+            name: name.to_string(),
+            team,
+            spymaster,
+            captain,
+        })])
+    }
+
+    pub fn chat(&mut self, channel: Channel, text: &str) -> Result<(), ClientError> {
+        self.send(vec![Step::Chat(Chat {
+            channel,
+            text: text.to_string(),
+        })])
+    }
+
+    pub fn reveal(&mut self, word: &str) -> Result<(), ClientError> {
+        self.send(vec![Step::Reveal(Reveal {
+            word: word.to_string(),
+        })])
+    }
+
+    pub fn vote(&mut self, word: &str) -> Result<(), ClientError> {
+        self.send(vec![Step::Vote(Vote {
+            word: word.to_string(),
+        })])
+    }
+
+    pub fn skip(&mut self) -> Result<(), ClientError> {
+        self.send(vec![Step::Skip])
+    }
+
+    pub fn spy(&mut self) -> Result<(), ClientError> {
+        self.send(vec![Step::Spy])
+    }
+
+    pub fn clue(&mut self, clue: Clue) -> Result<(), ClientError> {
+        self.send(vec![Step::Clue(clue)])
+    }
+
+    pub fn reset(&mut self) -> Result<(), ClientError> {
+        self.send(vec![Step::Reset(Reset)])?;
+        self.resetting = true;
+        Ok(())
+    }
+
+    /// Asks for the current state without changing anything, like the browser's polling.
+    pub fn refresh(&mut self) -> Result<(), ClientError> {
+        self.send(vec![])
+    }
+
+    /// The next message from the server, waiting at most `timeout`.
+    pub fn recv(&mut self, timeout: Duration) -> Result<ServerMsg, ClientError> {
+        match self.pending.pop_front() {
+            Some(msg) => Ok(msg),
+            None => self.receive(timeout),
+        }
+    }
+
+    /// The next message from the server if one has arrived.
+    pub fn try_recv(&mut self) -> Result<Option<ServerMsg>, ClientError> {
+        if let Some(msg) = self.pending.pop_front() {
+            return Ok(Some(msg));
+        }
+        loop {
+            let incoming = match self.incoming.try_recv() {
+                Ok(incoming) => incoming,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(ClientError::Closed),
+            };
+            if let Some(msg) = self.handle(incoming)? {
+                return Ok(Some(msg));
+            }
+        }
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<ServerMsg, ClientError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let incoming = match self.incoming.recv_timeout(left) {
+                Ok(incoming) => incoming,
+                Err(RecvTimeoutError::Timeout) => return Err(ClientError::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(ClientError::Closed),
+            };
+            if let Some(msg) = self.handle(incoming)? {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Updates the session state from a message, `None` for messages already seen.
+    fn handle(&mut self, incoming: Incoming) -> Result<Option<ServerMsg>, ClientError> {
+        let msg = match incoming {
+            Incoming::Message(msg) => msg,
+            Incoming::Invalid(e) => return Err(e.into()),
+            Incoming::Failed(e) => return Err(e.into()),
+            Incoming::Open(_) => return Ok(None),
+            Incoming::Closed => return Err(ClientError::Closed),
+        };
+        if let (Some(seq), Some(seen)) = (msg.seq, self.seq) {
+            if seq <= seen {
+                return Ok(None);
+            }
+        }
+        if msg.seq.is_some() {
+            self.seq = msg.seq;
+        }
+        for event in &msg.events {
+            match event {
+                Event::Session { id, resumed, seq, .. } => {
+                    self.session = Some(id.clone());
+                    if self.seq.is_none() || !resumed {
+                        self.seq = Some(*seq);
+                    }
+                }
+                // only the answer to this client's own reset names the next game's ident
+                Event::Reload { ident: Some(ident) } if self.resetting => {
+                    self.ident = ident.clone();
+                    self.resetting = false;
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(msg))
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.out.close(CloseCode::Normal);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn close(mut self) {
+        self.shutdown();
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Starts a connection thread and waits until the handshake is done.
+fn open(url: &str, encoding: Encoding, timeout: Duration) -> Result<(Sender, Receiver<Incoming>, JoinHandle<()>), ClientError> {
+    let (tx, rx) = channel();
+    let url = url::Url::parse(url).map_err(ClientError::Url)?;
+    let mut socket = Builder::new().build(move |out: Sender| Inbox {
+        out,
+        encoding,
+        incoming: tx.clone(),
+    })?;
+    socket.connect(url)?;
+    let thread = spawn(move || {
+        if let Err(e) = socket.run() {
+            debug!("Client connection ended: {:?}", e);
+        }
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(Incoming::Open(out)) => Ok((out, rx, thread)),
+        Ok(Incoming::Failed(e)) => Err(e.into()),
+        Ok(_) => Err(ClientError::Closed),
+        Err(RecvTimeoutError::Timeout) => Err(ClientError::Timeout),
+        Err(RecvTimeoutError::Disconnected) => Err(ClientError::Closed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::thread::spawn;
+    use std::time::Duration;
+
+    use ws::{Builder, CloseCode, Message, Sender};
+
+    use crate::client::Client;
+    use crate::protocol::codec::Encoding;
+    use crate::protocol::{Event, Msg, Role};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Answers the first message with a session and a replayed event, then repeats that event
+    /// and sends a reload that this client did not ask for.
+    fn server() -> String {
+        let socket = Builder::new().build(|out: Sender| move |message: Message| {
+            let msg = Msg::try_from(message).unwrap();
+            assert_eq!(msg.game, "game");
+            assert_eq!(msg.ident, "abc");
+            out.send(r#"{"game": "game", "steps": [{"type": "session", "id": "s1", "resumed": false, "seq": 3, "ident": "abc"}]}"#)?;
+            out.send(r#"{"game": "game", "seq": 3, "steps": [{"type": "turn", "team": "red"}]}"#)?;
+            out.send(r#"{"game": "game", "seq": 4, "steps": [{"type": "turn", "team": "blue"}, {"type": "reload", "ident": "xyz"}]}"#)?;
+            out.close(CloseCode::Normal)
+        }).unwrap().bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", socket.local_addr().unwrap());
+        spawn(move || socket.run());
+        url
+    }
+
+    #[test]
+    fn tracks_session_and_skips_seen_events() {
+        let mut client = Client::connect(&server(), "game", "abc", Role::Player, Encoding::Json, TIMEOUT).unwrap();
+        assert_eq!(client.session(), Some("s1"));
+        assert_eq!(client.ident(), "abc");
+        match &client.recv(TIMEOUT).unwrap().events[0] {
+            Event::Session { id, .. } => assert_eq!(id, "s1"),
+            event => panic!("expected the session, got {:?}", event),
+        }
+        let msg = client.recv(TIMEOUT).unwrap();
+        assert_eq!(msg.seq, Some(4));
+        assert_eq!(client.ident(), "abc");
+    }
+}
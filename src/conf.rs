@@ -1,16 +1,650 @@
-use config::Config;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use config::{Config, Environment, File};
+use log::LevelFilter;
+use serde::export::Formatter;
+
+pub const ENV_PREFIX: &'static str = "CODENAMER";
+const DEFAULT_SOCKET_URL: &'static str = "ws://localhost:9123";
 
 lazy_static! {
-    pub static ref CONFIG: Config = load_config();
+    static ref SETTINGS: Settings = match load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+}
+
+/// The effective configuration, loaded on first access. Exits the process if the configuration is invalid.
+pub fn settings() -> &'static Settings {
+    &SETTINGS
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheBackend {
+    Ram,
+    File,
+}
+
+impl Display for CacheBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CacheBackend::Ram => "ram",
+            CacheBackend::File => "file",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpSettings {
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct SocketSettings {
+    pub address: String,
+    pub port: u16,
+    pub url: String,
+    /// How long a disconnected client can resume its session.
+    pub session_ttl: Duration,
+    /// Broadcast events kept per game for reconnecting clients.
+    pub replay_events: usize,
+    /// How often the server pings each client.
+    pub ping_interval: Duration,
+    /// Connections that sent nothing, not even a pong, for this long are considered dead.
+    pub idle_timeout: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetSettings {
+    pub css: String,
+    pub js: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct WordSettings {
+    pub dirs: Vec<String>,
+    /// Number of previous games in a room whose words are kept off the next board.
+    pub recent_games: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheSettings {
+    pub backend: CacheBackend,
+    pub dir: String,
+    pub max_age: Duration,
+    pub cleanup_interval: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogSettings {
+    pub level: LevelFilter,
+    /// Per module levels, e.g. `codenamer::web::socket=debug`. The longest matching module wins.
+    pub filters: Vec<(String, LevelFilter)>,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShutdownSettings {
+    pub deadline: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct HealthSettings {
+    pub cache_lock_timeout: Duration,
+    pub max_restarts: usize,
+    pub restart_window: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatSettings {
+    /// Messages kept with each game, older ones are dropped.
+    pub history: usize,
+    pub max_length: usize,
+    /// Flood protection: at most `max_messages` per connection within `window`.
+    pub max_messages: usize,
+    pub window: Duration,
+    /// Whether spymasters may read and write their team's channel.
+    pub spymasters_read_team: bool,
+}
+
+/// A token bucket: up to `burst` at once, refilled by `per_minute`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rate {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LimitSettings {
+    /// Larger socket messages are rejected before parsing.
+    pub max_message_bytes: usize,
+    /// Socket steps per connection, a message without steps counts as one.
+    pub connection_steps: Rate,
+    /// Socket steps of all connections from one address.
+    pub ip_steps: Rate,
+    /// Games created from one address.
+    pub ip_games: Rate,
+    /// Addresses sending `parse_errors` unparsable messages within `parse_error_window` are banned for `ban`.
+    pub parse_errors: usize,
+    pub parse_error_window: Duration,
+    pub ban: Duration,
+    /// Proxies whose forwarding headers are believed, the peer address is used for everyone else.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BotSettings {
+    /// Directory with a word vector file `<language>.txt` per language, empty if there are none.
+    pub models_dir: String,
+    /// Most frequent words of a model that are considered as clues.
+    pub candidates: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub http: HttpSettings,
+    pub socket: SocketSettings,
+    pub assets: AssetSettings,
+    pub words: WordSettings,
+    pub cache: CacheSettings,
+    pub shutdown: ShutdownSettings,
+    pub log: LogSettings,
+    pub health: HealthSettings,
+    pub chat: ChatSettings,
+    pub limits: LimitSettings,
+    pub bot: BotSettings,
+}
+
+impl Settings {
+    /// Flat `key = value` view of the settings, using the same keys as the config file.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        vec![
+            ("http.address".into(), self.http.address.clone()),
+            ("http.port".into(), self.http.port.to_string()),
+            ("socket.address".into(), self.socket.address.clone()),
+            ("socket.port".into(), self.socket.port.to_string()),
+            ("socket.url".into(), self.socket.url.clone()),
+            ("socket.session_ttl_secs".into(), self.socket.session_ttl.as_secs().to_string()),
+            ("socket.replay_events".into(), self.socket.replay_events.to_string()),
+            ("socket.ping_interval_secs".into(), self.socket.ping_interval.as_secs().to_string()),
+            ("socket.idle_timeout_secs".into(), self.socket.idle_timeout.as_secs().to_string()),
+            ("assets.css".into(), self.assets.css.clone()),
+            ("assets.js".into(), self.assets.js.clone()),
+            ("words.dirs".into(), self.words.dirs.join(",")),
+            ("words.recent_games".into(), self.words.recent_games.to_string()),
+            ("cache.backend".into(), self.cache.backend.to_string()),
+            ("cache.dir".into(), self.cache.dir.clone()),
+            ("cache.max_age_secs".into(), self.cache.max_age.as_secs().to_string()),
+            ("cache.cleanup_interval_secs".into(), self.cache.cleanup_interval.as_secs().to_string()),
+            ("shutdown.deadline_secs".into(), self.shutdown.deadline.as_secs().to_string()),
+            ("log.level".into(), self.log.level.to_string().to_lowercase()),
+            ("log.filters".into(), self.log.filters.iter()
+                .map(|(module, level)| format!("{}={}", module, level.to_string().to_lowercase()))
+                .collect::<Vec<String>>()
+                .join(",")),
+            ("log.format".into(), self.log.format.to_string()),
+            ("health.cache_lock_timeout_ms".into(), self.health.cache_lock_timeout.as_millis().to_string()),
+            ("health.max_restarts".into(), self.health.max_restarts.to_string()),
+            ("health.restart_window_secs".into(), self.health.restart_window.as_secs().to_string()),
+            ("chat.history".into(), self.chat.history.to_string()),
+            ("chat.max_length".into(), self.chat.max_length.to_string()),
+            ("chat.max_messages".into(), self.chat.max_messages.to_string()),
+            ("chat.window_secs".into(), self.chat.window.as_secs().to_string()),
+            ("chat.spymasters_read_team".into(), self.chat.spymasters_read_team.to_string()),
+            ("limits.max_message_bytes".into(), self.limits.max_message_bytes.to_string()),
+            ("limits.connection_steps_burst".into(), self.limits.connection_steps.burst.to_string()),
+            ("limits.connection_steps_per_minute".into(), self.limits.connection_steps.per_minute.to_string()),
+            ("limits.ip_steps_burst".into(), self.limits.ip_steps.burst.to_string()),
+            ("limits.ip_steps_per_minute".into(), self.limits.ip_steps.per_minute.to_string()),
+            ("limits.ip_games_burst".into(), self.limits.ip_games.burst.to_string()),
+            ("limits.ip_games_per_minute".into(), self.limits.ip_games.per_minute.to_string()),
+            ("limits.parse_errors".into(), self.limits.parse_errors.to_string()),
+            ("limits.parse_error_window_secs".into(), self.limits.parse_error_window.as_secs().to_string()),
+            ("limits.ban_secs".into(), self.limits.ban.as_secs().to_string()),
+            ("limits.trusted_proxies".into(), self.limits.trusted_proxies.iter().map(IpAddr::to_string).collect::<Vec<String>>().join(",")),
+            ("bot.models_dir".into(), self.bot.models_dir.clone()),
+            ("bot.candidates".into(), self.bot.candidates.to_string()),
+        ]
+    }
+}
+
+impl Display for Settings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.entries() {
+            writeln!(f, "{} = {:?}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidConfigError(pub Vec<String>);
+
+impl Display for InvalidConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads `Config.toml` (if present) and `CODENAMER_*` environment overrides on top of the defaults.
+/// Nested keys are separated by a double underscore, e.g. `CODENAMER_HTTP__PORT=8080`.
+pub fn load() -> Result<Settings, InvalidConfigError> {
+    let mut config = Config::default();
+    let merged = defaults(&mut config)
+        .and_then(|_| config.merge(File::with_name("Config").required(false)).map(|_| ()))
+        .and_then(|_| config.merge(Environment::with_prefix(ENV_PREFIX).separator("__")).map(|_| ()));
+    if let Err(e) = merged {
+        return Err(InvalidConfigError(vec![e.to_string()]));
+    }
+    from_config(&config)
+}
+
+fn defaults(config: &mut Config) -> Result<(), config::ConfigError> {
+    config.set_default("http.address", "0.0.0.0")?;
+    config.set_default("http.port", 8000i64)?;
+    config.set_default("socket.address", "0.0.0.0")?;
+    config.set_default("socket.port", 9123i64)?;
+    config.set_default("socket.url", DEFAULT_SOCKET_URL)?;
+    config.set_default("socket.session_ttl_secs", 300i64)?;
+    config.set_default("socket.replay_events", 500i64)?;
+    config.set_default("socket.ping_interval_secs", 15i64)?;
+    config.set_default("socket.idle_timeout_secs", 45i64)?;
+    config.set_default("assets.css", "static/css")?;
+    config.set_default("assets.js", "static/js")?;
+    config.set_default("words.dirs", Vec::<String>::new())?;
+    config.set_default("words.recent_games", 2i64)?;
+    config.set_default("cache.backend", "ram")?;
+    config.set_default("cache.dir", "games")?;
+    config.set_default("cache.max_age_secs", 24 * 60 * 60i64)?;
+    config.set_default("cache.cleanup_interval_secs", 5i64)?;
+    config.set_default("shutdown.deadline_secs", 10i64)?;
+    config.set_default("log.level", "info")?;
+    config.set_default("log.filters", "")?;
+    config.set_default("log.format", "text")?;
+    config.set_default("health.cache_lock_timeout_ms", 500i64)?;
+    config.set_default("health.max_restarts", 5i64)?;
+    config.set_default("health.restart_window_secs", 60i64)?;
+    config.set_default("chat.history", 200i64)?;
+    config.set_default("chat.max_length", 500i64)?;
+    config.set_default("chat.max_messages", 5i64)?;
+    config.set_default("chat.window_secs", 10i64)?;
+    config.set_default("chat.spymasters_read_team", false)?;
+    config.set_default("limits.max_message_bytes", 16 * 1024i64)?;
+    config.set_default("limits.connection_steps_burst", 20i64)?;
+    config.set_default("limits.connection_steps_per_minute", 300i64)?;
+    config.set_default("limits.ip_steps_burst", 60i64)?;
+    config.set_default("limits.ip_steps_per_minute", 1200i64)?;
+    config.set_default("limits.ip_games_burst", 5i64)?;
+    config.set_default("limits.ip_games_per_minute", 10i64)?;
+    config.set_default("limits.parse_errors", 10i64)?;
+    config.set_default("limits.parse_error_window_secs", 60i64)?;
+    config.set_default("limits.ban_secs", 600i64)?;
+    config.set_default("limits.trusted_proxies", Vec::<String>::new())?;
+    config.set_default("bot.models_dir", "")?;
+    config.set_default("bot.candidates", 20_000i64)?;
+    Ok(())
 }
 
-fn load_config() -> Config {
-    let mut settings = config::Config::default();
-    settings
-        .merge(config::File::with_name("Config")).unwrap();
-    settings
+fn from_config(config: &Config) -> Result<Settings, InvalidConfigError> {
+    let mut problems = Vec::new();
+    let mut reader = Reader {
+        config,
+        problems: &mut problems,
+    };
+    // The legacy key only applies while socket.url is left at its default.
+    let socket_url = match (reader.string("socket.url"), config.get_str("websocket-url")) {
+        (url, Ok(legacy)) if url == DEFAULT_SOCKET_URL => legacy,
+        (url, _) => url,
+    };
+    let settings = Settings {
+        http: HttpSettings {
+            address: reader.string("http.address"),
+            port: reader.port("http.port"),
+        },
+        socket: SocketSettings {
+            address: reader.string("socket.address"),
+            port: reader.port("socket.port"),
+            url: socket_url,
+            session_ttl: reader.seconds("socket.session_ttl_secs"),
+            replay_events: reader.int("socket.replay_events", 0, 100_000) as usize,
+            ping_interval: reader.seconds("socket.ping_interval_secs"),
+            idle_timeout: reader.seconds("socket.idle_timeout_secs"),
+        },
+        assets: AssetSettings {
+            css: reader.directory("assets.css"),
+            js: reader.directory("assets.js"),
+        },
+        words: WordSettings {
+            dirs: reader.directories("words.dirs"),
+            recent_games: reader.int("words.recent_games", 0, 100) as usize,
+        },
+        cache: CacheSettings {
+            backend: reader.cache_backend("cache.backend"),
+            dir: reader.string("cache.dir"),
+            max_age: reader.seconds("cache.max_age_secs"),
+            cleanup_interval: reader.seconds("cache.cleanup_interval_secs"),
+        },
+        shutdown: ShutdownSettings {
+            deadline: reader.seconds("shutdown.deadline_secs"),
+        },
+        log: LogSettings {
+            level: reader.log_level("log.level"),
+            filters: reader.log_filters("log.filters"),
+            format: reader.log_format("log.format"),
+        },
+        health: HealthSettings {
+            cache_lock_timeout: Duration::from_millis(reader.int("health.cache_lock_timeout_ms", 1, 60_000) as u64),
+            max_restarts: reader.int("health.max_restarts", 0, 1000) as usize,
+            restart_window: reader.seconds("health.restart_window_secs"),
+        },
+        chat: ChatSettings {
+            history: reader.int("chat.history", 0, 10_000) as usize,
+            max_length: reader.int("chat.max_length", 1, 10_000) as usize,
+            max_messages: reader.int("chat.max_messages", 1, 1000) as usize,
+            window: reader.seconds("chat.window_secs"),
+            spymasters_read_team: reader.boolean("chat.spymasters_read_team"),
+        },
+        limits: LimitSettings {
+            max_message_bytes: reader.int("limits.max_message_bytes", 256, 16 * 1024 * 1024) as usize,
+            connection_steps: reader.rate("limits.connection_steps"),
+            ip_steps: reader.rate("limits.ip_steps"),
+            ip_games: reader.rate("limits.ip_games"),
+            parse_errors: reader.int("limits.parse_errors", 1, 10_000) as usize,
+            parse_error_window: reader.seconds("limits.parse_error_window_secs"),
+            ban: reader.seconds("limits.ban_secs"),
+            trusted_proxies: reader.addresses("limits.trusted_proxies"),
+        },
+        bot: BotSettings {
+            models_dir: reader.directory("bot.models_dir"),
+            candidates: reader.int("bot.candidates", 1, 1_000_000) as usize,
+        },
+    };
+    if !(settings.socket.url.starts_with("ws://") || settings.socket.url.starts_with("wss://")) {
+        problems.push(format!("socket.url: expected a ws:// or wss:// url, got {:?}", settings.socket.url));
+    }
+    if settings.socket.idle_timeout <= settings.socket.ping_interval {
+        problems.push(format!(
+            "socket.idle_timeout_secs: must be longer than socket.ping_interval_secs ({}), got {}",
+            settings.socket.ping_interval.as_secs(),
+            settings.socket.idle_timeout.as_secs()
+        ));
+    }
+    if problems.is_empty() {
+        Ok(settings)
+    } else {
+        Err(InvalidConfigError(problems))
+    }
 }
 
-pub fn websocket_url() -> String {
-    CONFIG.get_str("websocket-url").unwrap()
-}
\ No newline at end of file
+/// Reads single keys and records a problem for every key that is missing or invalid instead of stopping at the first.
+struct Reader<'a> {
+    config: &'a Config,
+    problems: &'a mut Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    fn problem(&mut self, key: &str, problem: String) {
+        self.problems.push(format!("{}: {}", key, problem));
+    }
+
+    fn string(&mut self, key: &str) -> String {
+        match self.config.get_str(key) {
+            Ok(value) => value,
+            Err(e) => {
+                self.problem(key, e.to_string());
+                String::new()
+            }
+        }
+    }
+
+    fn boolean(&mut self, key: &str) -> bool {
+        match self.config.get_bool(key) {
+            Ok(value) => value,
+            Err(e) => {
+                self.problem(key, e.to_string());
+                false
+            }
+        }
+    }
+
+    fn int(&mut self, key: &str, min: i64, max: i64) -> i64 {
+        match self.config.get_int(key) {
+            Ok(value) if value >= min && value <= max => value,
+            Ok(value) => {
+                self.problem(key, format!("expected a value between {} and {}, got {}", min, max, value));
+                min
+            }
+            Err(e) => {
+                self.problem(key, e.to_string());
+                min
+            }
+        }
+    }
+
+    fn port(&mut self, key: &str) -> u16 {
+        self.int(key, 1, std::u16::MAX as i64) as u16
+    }
+
+    /// Reads `<prefix>_burst` and `<prefix>_per_minute`.
+    fn rate(&mut self, prefix: &str) -> Rate {
+        Rate {
+            burst: self.int(&format!("{}_burst", prefix), 1, 1_000_000) as u32,
+            per_minute: self.int(&format!("{}_per_minute", prefix), 1, 1_000_000) as u32,
+        }
+    }
+
+    fn seconds(&mut self, key: &str) -> Duration {
+        Duration::from_secs(self.int(key, 1, std::i64::MAX) as u64)
+    }
+
+    fn directory(&mut self, key: &str) -> String {
+        let dir = self.string(key);
+        if !dir.is_empty() && !Path::new(&dir).is_dir() {
+            self.problem(key, format!("{:?} is not a directory", dir));
+        }
+        dir
+    }
+
+    /// Accepts a list from the config file or a comma separated string from the environment.
+    fn list(&mut self, key: &str) -> Vec<String> {
+        match self.config.get::<Vec<String>>(key) {
+            Ok(items) => items,
+            Err(_) => self.string(key)
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+        }
+    }
+
+    fn addresses(&mut self, key: &str) -> Vec<IpAddr> {
+        let mut addresses = vec![];
+        for item in self.list(key) {
+            match item.parse() {
+                Ok(address) => addresses.push(address),
+                Err(_) => self.problem(key, format!("{:?} is not an IP address", item)),
+            }
+        }
+        addresses
+    }
+
+    fn directories(&mut self, key: &str) -> Vec<String> {
+        let dirs = self.list(key);
+        for dir in &dirs {
+            if !Path::new(dir).is_dir() {
+                self.problem(key, format!("{:?} is not a directory", dir));
+            }
+        }
+        dirs
+    }
+
+    fn log_level(&mut self, key: &str) -> LevelFilter {
+        let level = self.string(key);
+        self.parse_level(key, &level)
+    }
+
+    fn parse_level(&mut self, key: &str, level: &str) -> LevelFilter {
+        match LevelFilter::from_str(level.trim()) {
+            Ok(level) => level,
+            Err(_) => {
+                self.problem(key, format!("unknown log level {:?}, expected off, error, warn, info, debug or trace", level));
+                LevelFilter::Info
+            }
+        }
+    }
+
+    /// Accepts `module=level` pairs, separated by commas.
+    fn log_filters(&mut self, key: &str) -> Vec<(String, LevelFilter)> {
+        let filters = self.string(key);
+        let mut parsed = Vec::new();
+        for filter in filters.split(',').map(|filter| filter.trim()).filter(|filter| !filter.is_empty()) {
+            let mut parts = filter.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(module), Some(level)) => {
+                    let level = self.parse_level(key, level);
+                    parsed.push((module.trim().to_string(), level));
+                }
+                _ => self.problem(key, format!("expected module=level, got {:?}", filter)),
+            }
+        }
+        parsed
+    }
+
+    fn log_format(&mut self, key: &str) -> LogFormat {
+        match self.string(key).to_lowercase().as_str() {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            other => {
+                self.problem(key, format!("unknown log format {:?}, expected \"text\" or \"json\"", other));
+                LogFormat::Text
+            }
+        }
+    }
+
+    fn cache_backend(&mut self, key: &str) -> CacheBackend {
+        match self.string(key).to_lowercase().as_str() {
+            "ram" => CacheBackend::Ram,
+            "file" => CacheBackend::File,
+            other => {
+                self.problem(key, format!("unknown cache backend {:?}, expected \"ram\" or \"file\"", other));
+                CacheBackend::Ram
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+    use log::LevelFilter;
+
+    use crate::conf::{defaults, from_config, CacheBackend};
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        defaults(&mut config).unwrap();
+        config
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let settings = from_config(&config()).unwrap();
+        assert_eq!(8000, settings.http.port);
+        assert_eq!(9123, settings.socket.port);
+        assert_eq!(CacheBackend::Ram, settings.cache.backend);
+        assert!(settings.words.dirs.is_empty());
+    }
+
+    #[test]
+    fn legacy_websocket_url() {
+        let mut config = config();
+        config.set("websocket-url", "ws://example.com:9123").unwrap();
+        let settings = from_config(&config).unwrap();
+        assert_eq!("ws://example.com:9123", settings.socket.url);
+        config.set("socket.url", "wss://codenames.example.com").unwrap();
+        let settings = from_config(&config).unwrap();
+        assert_eq!("wss://codenames.example.com", settings.socket.url);
+    }
+
+    #[test]
+    fn trusted_proxies() {
+        let mut config = config();
+        assert!(from_config(&config).unwrap().limits.trusted_proxies.is_empty());
+        config.set("limits.trusted_proxies", "127.0.0.1, ::1").unwrap();
+        let settings = from_config(&config).unwrap();
+        assert_eq!(vec!["127.0.0.1".parse::<std::net::IpAddr>().unwrap(), "::1".parse().unwrap()], settings.limits.trusted_proxies);
+        config.set("limits.trusted_proxies", "localhost").unwrap();
+        assert!(from_config(&config).is_err());
+    }
+
+    #[test]
+    fn comma_separated_word_dirs() {
+        let mut config = config();
+        config.set("words.dirs", "src, static").unwrap();
+        let settings = from_config(&config).unwrap();
+        assert_eq!(vec!["src".to_string(), "static".to_string()], settings.words.dirs);
+    }
+
+    #[test]
+    fn log_filters() {
+        let mut config = config();
+        config.set("log.filters", "codenamer::web=warn, rocket=off").unwrap();
+        let settings = from_config(&config).unwrap();
+        assert_eq!(vec![
+            ("codenamer::web".to_string(), LevelFilter::Warn),
+            ("rocket".to_string(), LevelFilter::Off),
+        ], settings.log.filters);
+    }
+
+    #[test]
+    fn reports_every_invalid_key() {
+        let mut config = config();
+        config.set("http.port", 0i64).unwrap();
+        config.set("socket.port", "many").unwrap();
+        config.set("socket.url", "http://localhost").unwrap();
+        config.set("cache.backend", "floppy").unwrap();
+        let problems = from_config(&config).unwrap_err().0;
+        assert_eq!(4, problems.len());
+        assert!(problems[0].starts_with("http.port"));
+        assert!(problems[1].starts_with("socket.port"));
+        assert!(problems[2].starts_with("cache.backend"));
+        assert!(problems[3].starts_with("socket.url"));
+    }
+
+    #[test]
+    fn idle_timeout_exceeds_ping_interval() {
+        let mut config = config();
+        config.set("socket.ping_interval_secs", 30i64).unwrap();
+        config.set("socket.idle_timeout_secs", 30i64).unwrap();
+        let problems = from_config(&config).unwrap_err().0;
+        assert_eq!(1, problems.len());
+        assert!(problems[0].starts_with("socket.idle_timeout_secs"));
+    }
+}
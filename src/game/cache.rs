@@ -1,24 +1,42 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 use itertools::Itertools;
 
-use crate::game::cache::GameSessionCacheError::{GameDoesNotExistsError, GameNameTakenError};
+use crate::conf::{CacheBackend, CacheSettings};
+use crate::game::cache::GameSessionCacheError::{GameDoesNotExistsError, GameNameTakenError, StorageError};
 use crate::game::Game;
+use crate::metrics::METRICS;
 
 #[derive(Debug)]
 pub enum GameSessionCacheError {
     GameNameTakenError(String),
     GameDoesNotExistsError(String),
+    StorageError(String),
 }
 
 pub trait GameSessionCache {
     fn count(&self) -> usize;
+    fn names(&self) -> Vec<String>;
     fn by_name(&self, name: &str) -> Option<Arc<Mutex<Game>>>;
     fn put(&mut self, game: Game) -> Result<(), GameSessionCacheError>;
     fn delete(&mut self, name: &str) -> Result<(), GameSessionCacheError>;
     fn cleanup(&mut self, max_age: &Duration);
+    /// Writes the current state of all games to persistent storage, if the cache has any.
+    fn flush(&mut self) -> Result<(), GameSessionCacheError> {
+        Ok(())
+    }
+}
+
+/// Creates the cache backend selected in the configuration.
+pub fn from_settings(settings: &CacheSettings) -> Result<Box<dyn GameSessionCache + Send>, GameSessionCacheError> {
+    Ok(match settings.backend {
+        CacheBackend::Ram => Box::new(RamGameCache::new()),
+        CacheBackend::File => Box::new(FileGameCache::open(Path::new(&settings.dir))?),
+    })
 }
 
 pub struct RamGameCache {
@@ -38,6 +56,10 @@ impl GameSessionCache for RamGameCache {
         self.games.lock().unwrap().keys().count()
     }
 
+    fn names(&self) -> Vec<String> {
+        self.games.lock().unwrap().keys().cloned().collect()
+    }
+
     fn by_name(&self, name: &str) -> Option<Arc<Mutex<Game>>> {
         self.games.lock().unwrap().get(name).cloned()
     }
@@ -53,7 +75,7 @@ impl GameSessionCache for RamGameCache {
     }
 
     fn delete(&mut self, name: &str) -> Result<(), GameSessionCacheError> {
-        println!("Game {} will be removed", name);
+        debug!("Game {} will be removed", name);
         if self.games.lock().unwrap().remove(name).is_some() {
             Ok(())
         } else {
@@ -74,6 +96,148 @@ impl GameSessionCache for RamGameCache {
         }.into_iter().unique().collect();
         obsolete.iter().for_each(|name| {
             self.delete(name).unwrap();
+            METRICS.games_expired.inc();
         });
     }
-}
\ No newline at end of file
+}
+
+/// Keeps games in memory like `RamGameCache`, but stores every game as a JSON file in `dir`
+/// so they survive restarts. Changes made to games are written on `flush`.
+pub struct FileGameCache {
+    dir: PathBuf,
+    ram: RamGameCache,
+}
+
+impl FileGameCache {
+    pub fn open(dir: &Path) -> Result<Self, GameSessionCacheError> {
+        fs::create_dir_all(dir).map_err(|e| StorageError(format!("{}: {}", dir.display(), e)))?;
+        let mut ram = RamGameCache::new();
+        for game in FileGameCache::read_games(dir)? {
+            ram.put(game)?;
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            ram,
+        })
+    }
+
+    /// Reads all stored games from `dir` without keeping them around, skipping files that cannot be read.
+    pub fn read_games(dir: &Path) -> Result<Vec<Game>, GameSessionCacheError> {
+        let entries = fs::read_dir(dir).map_err(|e| StorageError(format!("{}: {}", dir.display(), e)))?;
+        let mut games = Vec::new();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+                continue;
+            }
+            let game = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| serde_json::from_str::<Game>(&data).map_err(|e| e.to_string()));
+            match game {
+                Ok(game) => games.push(game),
+                Err(e) => warn!("Skipping stored game {}: {}", path.display(), e),
+            }
+        }
+        Ok(games)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    fn write(&self, game: &Game) -> Result<(), GameSessionCacheError> {
+        let path = self.path(&game.name);
+        let data = serde_json::to_string(game).map_err(|e| StorageError(format!("{}: {}", game.name, e)))?;
+        fs::write(&path, data).map_err(|e| StorageError(format!("{}: {}", path.display(), e)))
+    }
+}
+
+impl GameSessionCache for FileGameCache {
+    fn count(&self) -> usize {
+        self.ram.count()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.ram.names()
+    }
+
+    fn by_name(&self, name: &str) -> Option<Arc<Mutex<Game>>> {
+        self.ram.by_name(name)
+    }
+
+    fn put(&mut self, game: Game) -> Result<(), GameSessionCacheError> {
+        let name = game.name.clone();
+        self.ram.put(game)?;
+        let game = self.ram.by_name(&name).unwrap();
+        let game = game.lock().unwrap();
+        self.write(&game)
+    }
+
+    fn delete(&mut self, name: &str) -> Result<(), GameSessionCacheError> {
+        self.ram.delete(name)?;
+        let path = self.path(name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| StorageError(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    fn cleanup(&mut self, max_age: &Duration) {
+        let before = self.ram.names();
+        self.ram.cleanup(max_age);
+        let after = self.ram.names();
+        before.iter()
+            .filter(|name| !after.contains(name))
+            .for_each(|name| {
+                let _ = fs::remove_file(self.path(name));
+            });
+    }
+
+    fn flush(&mut self) -> Result<(), GameSessionCacheError> {
+        for name in self.ram.names() {
+            if let Some(game) = self.ram.by_name(&name) {
+                let game = game.lock().unwrap().clone();
+                self.write(&game)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::game::cache::{FileGameCache, GameSessionCache};
+    use crate::game::Game;
+    use crate::game::options::GameOptions;
+
+    #[test]
+    fn file_cache_survives_reopen() {
+        let dir = std::env::temp_dir().join(format!("codenamer-games-{}", uuid::Uuid::new_v4()));
+        {
+            let mut cache = FileGameCache::open(&dir).unwrap();
+            cache.put(Game::new("lunch".into(), GameOptions::language("english")).unwrap()).unwrap();
+            cache.by_name("lunch").unwrap().lock().unwrap().winner = Some(crate::game::Color::Blue);
+            cache.flush().unwrap();
+        }
+        let cache = FileGameCache::open(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(vec!["lunch".to_string()], cache.names());
+        assert_eq!(Some(crate::game::Color::Blue), cache.by_name("lunch").unwrap().lock().unwrap().winner);
+    }
+
+    #[test]
+    fn unreadable_games_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("codenamer-games-{}", uuid::Uuid::new_v4()));
+        {
+            let mut cache = FileGameCache::open(&dir).unwrap();
+            cache.put(Game::new("lunch".into(), GameOptions::language("english")).unwrap()).unwrap();
+            cache.flush().unwrap();
+        }
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        let cache = FileGameCache::open(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(vec!["lunch".to_string()], cache.names());
+    }
+}
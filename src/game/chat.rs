@@ -0,0 +1,177 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::export::Formatter;
+
+use crate::game::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Everyone in the game, including spectators.
+    Global,
+    /// Only the sender's team.
+    Team,
+}
+
+impl Channel {
+    pub fn parse(value: &str) -> Option<Channel> {
+        match value {
+            "global" => Some(Channel::Global),
+            "team" => Some(Channel::Team),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Channel::Global => "global",
+            Channel::Team => "team",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub channel: Channel,
+    pub text: String,
+    pub sent: SystemTime,
+}
+
+/// Who wants to read or write a message: their team and whether they are spymaster, `None` for spectators.
+pub type Reader<'a> = Option<(&'a Color, bool)>;
+
+impl ChatMessage {
+    pub fn visible_to(&self, reader: Reader, spymasters_read_team: bool) -> bool {
+        match self.channel {
+            Channel::Global => true,
+            Channel::Team => match reader {
+                Some((team, spymaster)) => *team == self.team && (!spymaster || spymasters_read_team),
+                None => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChatError {
+    Empty,
+    TooLong(usize),
+    NotSeated,
+    SpymasterInTeamChannel,
+    Flooding,
+}
+
+impl Display for ChatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChatError::Empty => write!(f, "Empty messages are not sent."),
+            ChatError::TooLong(max) => write!(f, "Messages can have at most {} characters.", max),
+            ChatError::NotSeated => write!(f, "Join a team to chat."),
+            ChatError::SpymasterInTeamChannel => write!(f, "Spymasters cannot take part in the team discussion."),
+            ChatError::Flooding => write!(f, "You are sending messages too fast."),
+        }
+    }
+}
+
+/// Trims `text` and checks its length.
+pub fn validate_text(text: &str, max_length: usize) -> Result<String, ChatError> {
+    let text = text.trim();
+    if text.is_empty() {
+        Err(ChatError::Empty)
+    } else if text.chars().count() > max_length {
+        Err(ChatError::TooLong(max_length))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+/// Appends `message` and drops the oldest messages beyond `history`.
+pub fn record(chat: &mut Vec<ChatMessage>, message: ChatMessage, history: usize) {
+    chat.push(message);
+    if chat.len() > history {
+        let excess = chat.len() - history;
+        chat.drain(..excess);
+    }
+}
+
+/// Allows at most `max` messages within any `window`.
+#[derive(Debug, Default)]
+pub struct FloodGuard {
+    sent: Vec<Instant>,
+}
+
+impl FloodGuard {
+    pub fn allow(&mut self, now: Instant, max: usize, window: Duration) -> bool {
+        self.sent.retain(|sent| now.duration_since(*sent) < window);
+        if self.sent.len() >= max {
+            false
+        } else {
+            self.sent.push(now);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use crate::game::chat::{record, validate_text, Channel, ChatError, ChatMessage, FloodGuard};
+    use crate::game::Color;
+
+    fn message(channel: Channel, text: &str) -> ChatMessage {
+        ChatMessage {
+            name: "Ann".into(),
+            team: Color::Red,
+            spymaster: false,
+            channel,
+            text: text.into(),
+            sent: SystemTime::now(),
+        }
+    }
+
+    #[test_case(Channel::Global, None, false => true)]
+    #[test_case(Channel::Team, None, false => false)]
+    #[test_case(Channel::Team, Some((Color::Red, false)), false => true)]
+    #[test_case(Channel::Team, Some((Color::Blue, false)), false => false)]
+    #[test_case(Channel::Team, Some((Color::Red, true)), false => false)]
+    #[test_case(Channel::Team, Some((Color::Red, true)), true => true)]
+    fn visibility(channel: Channel, reader: Option<(Color, bool)>, spymasters_read_team: bool) -> bool {
+        let reader = reader.as_ref().map(|(team, spymaster)| (team, *spymaster));
+        message(channel, "hi").visible_to(reader, spymasters_read_team)
+    }
+
+    #[test_case(" hi " => Ok("hi".to_string()))]
+    #[test_case("   " => Err(ChatError::Empty))]
+    #[test_case("too long" => Err(ChatError::TooLong(5)))]
+    fn validate(text: &str) -> Result<String, ChatError> {
+        validate_text(text, 5)
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut chat = vec![];
+        for text in &["a", "b", "c"] {
+            record(&mut chat, message(Channel::Global, text), 2);
+        }
+        let texts: Vec<&str> = chat.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(vec!["b", "c"], texts);
+    }
+
+    #[test]
+    fn flood_guard_limits_within_window() {
+        let mut guard = FloodGuard::default();
+        let now = Instant::now();
+        let window = Duration::from_secs(10);
+        assert!(guard.allow(now, 2, window));
+        assert!(guard.allow(now, 2, window));
+        assert!(!guard.allow(now, 2, window));
+        assert!(guard.allow(now + Duration::from_secs(11), 2, window));
+    }
+}
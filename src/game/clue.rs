@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+use serde::export::Formatter;
+
+use crate::game::Game;
+use crate::res::validate::normalize;
+
+/// Largest number a spymaster may attach to a clue.
+pub const MAX_COUNT: usize = 9;
+
+/// A spymaster's hint: one word and the number of cards it relates to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clue {
+    pub word: String,
+    pub count: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ClueError {
+    /// Not a word followed by a number.
+    Format,
+    Count(usize),
+    /// The clue is or contains a card that is still hidden.
+    OnBoard(String),
+    /// The team whose turn it is already has a clue.
+    Given,
+    Finished,
+}
+
+impl Display for ClueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClueError::Format => write!(f, "Expected one word and a number, like 'ocean 2'."),
+            ClueError::Count(count) => write!(f, "A clue can relate to at most {} cards, got {}.", MAX_COUNT, count),
+            ClueError::OnBoard(word) => write!(f, "The clue may not name the card '{}'.", word),
+            ClueError::Given => write!(f, "There already is a clue for this turn."),
+            ClueError::Finished => write!(f, "The game is over."),
+        }
+    }
+}
+
+impl Clue {
+    /// Parses a clue written as `word count`.
+    pub fn parse(text: &str) -> Result<Clue, ClueError> {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        match parts.as_slice() {
+            [word, count] => {
+                let count = count.parse::<usize>().map_err(|_| ClueError::Format)?;
+                if count > MAX_COUNT {
+                    return Err(ClueError::Count(count));
+                }
+                Ok(Clue {
+                    word: normalize(word),
+                    count,
+                })
+            }
+            _ => Err(ClueError::Format),
+        }
+    }
+
+    /// Checks that the clue is a single word that gives away no hidden card by naming it.
+    pub fn check(&self, game: &Game) -> Result<(), ClueError> {
+        if self.word.is_empty() || self.word.contains(char::is_whitespace) {
+            return Err(ClueError::Format);
+        }
+        if self.count > MAX_COUNT {
+            return Err(ClueError::Count(self.count));
+        }
+        self.check_hidden(game.words.iter().filter(|w| !w.opened).map(|w| w.word.as_str()))
+    }
+
+    /// Checks that the clue neither is nor contains one of the `hidden` cards, and the other way round.
+    pub fn check_hidden<'a, I: IntoIterator<Item = &'a str>>(&self, hidden: I) -> Result<(), ClueError> {
+        let clue = self.word.to_lowercase();
+        match hidden.into_iter().find(|word| {
+            let word = word.to_lowercase();
+            clue.contains(&word) || word.contains(&clue)
+        }) {
+            Some(word) => Err(ClueError::OnBoard(word.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Cards the operatives may reveal for this clue, one more than the count, unlimited for zero.
+    pub fn guesses(&self) -> Option<usize> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.count + 1)
+        }
+    }
+}
+
+impl Display for Clue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.word, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::clue::{Clue, ClueError};
+    use crate::game::options::GameOptions;
+    use crate::game::Game;
+
+    #[test_case("ocean 2" => Ok(Clue { word: "ocean".into(), count: 2 }))]
+    #[test_case(" ocean  0 " => Ok(Clue { word: "ocean".into(), count: 0 }))]
+    #[test_case("ocean" => Err(ClueError::Format))]
+    #[test_case("deep ocean 2" => Err(ClueError::Format))]
+    #[test_case("ocean two" => Err(ClueError::Format))]
+    #[test_case("ocean 10" => Err(ClueError::Count(10)))]
+    fn parse(text: &str) -> Result<Clue, ClueError> {
+        Clue::parse(text)
+    }
+
+    #[test]
+    fn hidden_cards_may_not_be_named() {
+        let mut game = Game::new("test".into(), GameOptions::language("german")).unwrap();
+        for (i, w) in game.words.iter_mut().enumerate() {
+            w.word = format!("card{}", i);
+        }
+        game.words[0].word = "Ocean".into();
+        assert_eq!(Err(ClueError::OnBoard("Ocean".into())), Clue { word: "OCEAN".into(), count: 1 }.check(&game));
+        assert_eq!(Err(ClueError::OnBoard("Ocean".into())), Clue { word: "oceans".into(), count: 1 }.check(&game));
+        game.words[0].opened = true;
+        assert_eq!(Ok(()), Clue { word: "ocean".into(), count: 1 }.check(&game));
+    }
+
+    #[test_case("sea", 10 => Err(ClueError::Count(10)) ; "count too large")]
+    #[test_case("deep sea", 1 => Err(ClueError::Format) ; "two words")]
+    #[test_case(" ", 1 => Err(ClueError::Format) ; "blank")]
+    fn check_clues_not_parsed_from_text(word: &str, count: usize) -> Result<(), ClueError> {
+        let game = Game::new("test".into(), GameOptions::language("german")).unwrap();
+        Clue { word: word.into(), count }.check(&game)
+    }
+
+    #[test_case(0 => None)]
+    #[test_case(2 => Some(3))]
+    fn guesses(count: usize) -> Option<usize> {
+        Clue { word: "ocean".into(), count }.guesses()
+    }
+}
@@ -2,19 +2,27 @@ use std::fmt::Display;
 use std::time::SystemTime;
 
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use serde::export::Formatter;
 
+use crate::game::chat::ChatMessage;
+use crate::game::clue::{Clue, ClueError};
 use crate::game::Color::{Blue, Red};
 use crate::game::RevealOutcome::{Nop, Opened};
-#[cfg(debug)]
-use crate::print::ColoredDesc;
-use crate::res::words::NoSuchLanguageError;
+use crate::res::validate::normalize;
+use crate::game::options::GameOptions;
+use crate::game::vote::Ballot;
+use crate::res::words::WordSelectionError;
 use uuid::Uuid;
 
 pub mod cache;
+pub mod chat;
+pub mod clue;
+pub mod name;
+pub mod options;
+pub mod vote;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Color {
     Red,
     Blue,
@@ -35,18 +43,21 @@ impl Display for Color {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Team {
     Player(Color),
     None,
     Death,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameWord {
     pub word: String,
     pub team: Team,
     pub opened: bool,
+    /// Language the word was drawn from, `None` for custom words.
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -55,29 +66,78 @@ pub enum RevealOutcome {
     Opened(String, Team),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     pub name: String,
     pub ident: String,
+    /// Handed to the watch page instead of `ident`, kept for the whole room so watch links stay valid.
+    #[serde(default = "new_ident")]
+    pub watch_ident: String,
+    #[serde(alias = "language", deserialize_with = "options::stored")]
+    pub options: GameOptions,
     pub created: SystemTime,
     pub words: Vec<GameWord>,
     pub turn: Color,
     pub winner: Option<Color>,
+    /// Words of the previous games in this room, newest first, kept off the board where possible.
+    #[serde(default)]
+    pub recent_words: Vec<Vec<String>>,
+    /// Chat of this room, kept across games.
+    #[serde(default)]
+    pub chat: Vec<ChatMessage>,
+    /// Votes for the current turn in voting mode.
+    #[serde(default)]
+    pub ballot: Ballot,
+    /// The spymaster's clue for the current turn, if one was given.
+    #[serde(default)]
+    pub clue: Option<Clue>,
+    /// Cards the current team revealed for the clue so far.
+    #[serde(default)]
+    pub guessed: usize,
 }
 
 impl Game {
-    pub fn new(name: String, language: &str) -> Result<Self, NoSuchLanguageError> {
+    pub fn new(name: String, options: GameOptions) -> Result<Self, WordSelectionError> {
+        Game::with_rng(name, options, &mut thread_rng())
+    }
+
+    /// Like `Game::new`, but draws and places the words with the given random number generator.
+    pub fn with_rng<R: Rng>(name: String, options: GameOptions, rng: &mut R) -> Result<Self, WordSelectionError> {
+        Game::with_recent_words(name, options, vec![], rng)
+    }
+
+    fn with_recent_words<R: Rng>(name: String, options: GameOptions, recent_words: Vec<Vec<String>>, rng: &mut R) -> Result<Self, WordSelectionError> {
         let game = Game {
             name,
-            ident: Uuid::new_v4().to_string(),
+            ident: new_ident(),
+            watch_ident: new_ident(),
             created: SystemTime::now(),
-            words: words_for_game(language)?,
+            words: words_for_game(&options, &recent_words, rng)?,
+            options,
             turn: Red,
             winner: None,
+            recent_words,
+            chat: vec![],
+            ballot: Ballot::default(),
+            clue: None,
+            guessed: 0,
         };
-        #[cfg(debug)] {
-            println!("{}", game.desc_colored());
-        }
+        debug!("Created game {} in {}", &game.name, &game.options.words.language);
+        Ok(game)
+    }
+
+    /// Starts a fresh game in the same room, keeping name and options and avoiding the words of recent games.
+    pub fn next(&self) -> Result<Self, WordSelectionError> {
+        self.next_with_rng(crate::conf::settings().words.recent_games, &mut thread_rng())
+    }
+
+    fn next_with_rng<R: Rng>(&self, recent_games: usize, rng: &mut R) -> Result<Self, WordSelectionError> {
+        let mut recent_words = vec![self.words.iter().map(|w| w.word.clone()).collect()];
+        recent_words.extend(self.recent_words.iter().cloned());
+        recent_words.truncate(recent_games);
+        let mut game = Game::with_recent_words(self.name.clone(), self.options.clone(), recent_words, rng)?;
+        game.chat = self.chat.clone();
+        game.watch_ident = self.watch_ident.clone();
         Ok(game)
     }
 
@@ -85,35 +145,73 @@ impl Game {
         self.ident.eq(ident)
     }
 
+    pub fn matches_watch_ident(&self, ident: &str) -> bool {
+        self.watch_ident.eq(ident)
+    }
+
     pub fn reveal(&mut self, word: &str) -> RevealOutcome {
         if self.winner.is_some() {
             return Nop;
         }
         let mut outcome = Nop;
-        let word = self.words.iter_mut().find(|w| w.word.eq(word));
+        let word = normalize(word);
+        let word = self.words.iter_mut().find(|w| w.word.eq(&word));
+        let mut ends_turn = false;
         if let Some(w) = word {
             if !w.opened {
                 w.opened = true;
                 match &w.team {
-                    Team::Player(color) => {
-                        if color.ne(&self.turn) {
-                            self.turn = self.turn.invert();
-                        }
-                    }
-                    Team::None => self.turn = self.turn.invert(),
+                    Team::Player(color) => ends_turn = color.ne(&self.turn),
+                    Team::None => ends_turn = true,
                     Team::Death => self.winner = Some(self.turn.invert()),
                 }
 
                 outcome = Opened(w.word.clone(), w.team.clone());
+                self.ballot.clear();
             }
         }
         let winner = self.determine_winner();
         self.winner = winner;
+        if ends_turn {
+            self.skip();
+        } else if outcome.ne(&Nop) && self.winner.is_none() {
+            self.guessed += 1;
+            if self.guesses_left() == Some(0) {
+                self.skip();
+            }
+        }
         outcome
     }
 
+    /// Ends the current team's turn without revealing a card.
+    pub fn skip(&mut self) {
+        self.turn = self.turn.invert();
+        self.ballot.clear();
+        self.clue = None;
+        self.guessed = 0;
+    }
+
+    /// Sets the clue for the current team's turn.
+    pub fn give_clue(&mut self, clue: Clue) -> Result<(), ClueError> {
+        if self.winner.is_some() {
+            return Err(ClueError::Finished);
+        }
+        if self.clue.is_some() {
+            return Err(ClueError::Given);
+        }
+        clue.check(self)?;
+        self.clue = Some(clue);
+        Ok(())
+    }
+
+    /// Cards the current team may still reveal, `None` without a limit.
+    pub fn guesses_left(&self) -> Option<usize> {
+        self.clue.as_ref()
+            .and_then(Clue::guesses)
+            .map(|guesses| guesses.saturating_sub(self.guessed))
+    }
+
     fn determine_winner(&self) -> Option<Color> {
-        println!("winner is: {:?}", &self.winner);
         if self.winner.is_some() {
             self.winner.clone()
         } else {
@@ -124,7 +222,6 @@ impl Game {
                     .filter(|w| w.opened)
                     .filter(|w| w.team.eq(&team))
                     .count();
-                println!("{} opened: {}", &color, &revealed);
                 if revealed >= number {
                     return Some(color);
                 }
@@ -134,20 +231,26 @@ impl Game {
     }
 }
 
-fn words_for_game(language: &str) -> Result<Vec<GameWord>, NoSuchLanguageError> {
+fn new_ident() -> String {
+    Uuid::new_v4().to_string()
+}
+
+fn words_for_game<R: Rng>(options: &GameOptions, recent_words: &[Vec<String>], rng: &mut R) -> Result<Vec<GameWord>, WordSelectionError> {
     use Color::*;
     use Team::*;
 
-    let raw_words = crate::res::words::get_25_random(language)?;
+    let raw_words = crate::res::words::select_words(&options.words, 25, recent_words, rng)?;
     let indices: Vec<usize> = (0..25).collect();
     let mut words: Vec<GameWord> = Vec::new();
     let mut team_count = 0;
     let mut team = Player(Red);
     for i in indices {
+        let drawn = raw_words.get(i).unwrap().clone();
         words.push(GameWord {
-            word: raw_words.get(i).unwrap().clone(),
+            word: drawn.word,
             team: team.clone(),
             opened: false,
+            language: drawn.language,
         });
         team_count += 1;
         if team_count == number_of_words_for_team(&team) {
@@ -160,7 +263,7 @@ fn words_for_game(language: &str) -> Result<Vec<GameWord>, NoSuchLanguageError>
             }
         }
     }
-    words.shuffle(&mut thread_rng());
+    words.shuffle(rng);
     Ok(words)
 }
 
@@ -198,10 +301,11 @@ mod tests {
     mod game {
         use crate::game::Color::*;
         use crate::game::{Game, Color, Team};
+        use crate::game::options::GameOptions;
 
         #[test]
         fn determine_existing_winner() {
-            let mut game = Game::new("test".into(), "german").unwrap();
+            let mut game = Game::new("test".into(), GameOptions::language("german")).unwrap();
             game.winner = Some(Red);
             assert_eq!(game.winner, game.determine_winner());
         }
@@ -209,12 +313,80 @@ mod tests {
         #[test_case(Color::Red; "red")]
         #[test_case(Color::Blue; "blue")]
         fn determine_winner_by_revealed_cards(color: Color) {
-            let mut game = Game::new("test".into(), "german").unwrap();
+            let mut game = Game::new("test".into(), GameOptions::language("german")).unwrap();
             game.winner = None;
             open_all_with_color(&mut game, color.clone());
             assert_eq!(Some(color), game.determine_winner());
         }
 
+        #[test]
+        fn clue_limits_guesses() {
+            use crate::game::clue::{Clue, ClueError};
+
+            let mut game = Game::new("test".into(), GameOptions::language("german")).unwrap();
+            let red: Vec<String> = game.words.iter()
+                .filter(|w| w.team == Team::Player(Red))
+                .map(|w| w.word.clone())
+                .collect();
+            game.give_clue(Clue { word: "zzzz".into(), count: 1 }).unwrap();
+            assert_eq!(Err(ClueError::Given), game.give_clue(Clue { word: "yyyy".into(), count: 1 }));
+            game.reveal(&red[0]);
+            assert_eq!((Red, Some(1)), (game.turn.clone(), game.guesses_left()));
+            game.reveal(&red[1]);
+            assert_eq!((Blue, None), (game.turn.clone(), game.guesses_left()));
+            assert_eq!(None, game.clue);
+        }
+
+        #[test]
+        fn same_seed_same_board() {
+            use rand::rngs::StdRng;
+            use rand::SeedableRng;
+
+            let a = Game::with_rng("a".into(), GameOptions::language("english"), &mut StdRng::seed_from_u64(7)).unwrap();
+            let b = Game::with_rng("b".into(), GameOptions::language("english"), &mut StdRng::seed_from_u64(7)).unwrap();
+            let words = |g: &Game| g.words.iter().map(|w| w.word.clone()).collect::<Vec<String>>();
+            assert_eq!(words(&a), words(&b));
+        }
+
+        #[test]
+        fn next_remembers_recent_boards() {
+            use rand::rngs::StdRng;
+            use rand::SeedableRng;
+
+            let mut rng = StdRng::seed_from_u64(7);
+            let words = |g: &Game| g.words.iter().map(|w| w.word.clone()).collect::<Vec<String>>();
+            let first = Game::with_rng("a".into(), GameOptions::language("english"), &mut rng).unwrap();
+            let second = first.next_with_rng(2, &mut rng).unwrap();
+            let third = second.next_with_rng(2, &mut rng).unwrap();
+            let fourth = third.next_with_rng(2, &mut rng).unwrap();
+            assert_eq!(vec![words(&first)], second.recent_words);
+            assert_eq!(vec![words(&third), words(&second)], fourth.recent_words);
+            assert!(first.next_with_rng(0, &mut rng).unwrap().recent_words.is_empty());
+        }
+
+        #[test]
+        fn next_keeps_watch_ident() {
+            let first = Game::new("a".into(), GameOptions::language("english")).unwrap();
+            let second = first.next_with_rng(0, &mut rand::thread_rng()).unwrap();
+            assert_ne!(first.ident, second.ident);
+            assert_ne!(first.ident, first.watch_ident);
+            assert!(second.matches_watch_ident(&first.watch_ident));
+        }
+
+        #[test]
+        fn read_game_stored_with_language() {
+            const STORED: &str = r#"{"name":"lunch","ident":"1234","language":"english",
+                "created":{"secs_since_epoch":1600000000,"nanos_since_epoch":0},
+                "words":[{"word":"Ocean","team":{"Player":"Red"},"opened":true},{"word":"Fire","team":"Death","opened":false}],
+                "turn":"Blue","winner":null}"#;
+            let game: Game = serde_json::from_str(STORED).unwrap();
+            assert_eq!(GameOptions::language("english"), game.options);
+            assert_eq!(Blue, game.turn);
+            let game: Game = serde_json::from_str(&serde_json::to_string(&game).unwrap()).unwrap();
+            assert_eq!(GameOptions::language("english"), game.options);
+            assert_eq!(vec!["Ocean".to_string(), "Fire".into()], game.words.iter().map(|w| w.word.clone()).collect::<Vec<String>>());
+        }
+
         pub fn open_all_with_color(game: &mut Game, color: Color) {
             let team = Team::Player(color);
             game.words.iter_mut()
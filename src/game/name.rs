@@ -0,0 +1,63 @@
+use std::fmt::Display;
+
+use serde::export::Formatter;
+
+pub const MIN_LENGTH: usize = 3;
+pub const MAX_LENGTH: usize = 40;
+
+const RESERVED: [&'static str; 8] = ["admin", "api", "create", "css", "js", "metrics", "new", "static"];
+
+#[derive(Debug, PartialEq)]
+pub enum GameNameError {
+    TooShort(usize),
+    TooLong(usize),
+    InvalidCharacter(char),
+    Reserved(String),
+}
+
+impl Display for GameNameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameNameError::TooShort(len) =>
+                write!(f, "Game names need at least {} characters, got {}.", MIN_LENGTH, len),
+            GameNameError::TooLong(len) =>
+                write!(f, "Game names can have at most {} characters, got {}.", MAX_LENGTH, len),
+            GameNameError::InvalidCharacter(c) =>
+                write!(f, "Game names may only contain letters, digits, '-' and '_', found '{}'.", c),
+            GameNameError::Reserved(name) =>
+                write!(f, "The game name '{}' is reserved.", name),
+        }
+    }
+}
+
+pub fn validate(name: &str) -> Result<(), GameNameError> {
+    let len = name.chars().count();
+    if len < MIN_LENGTH {
+        return Err(GameNameError::TooShort(len));
+    }
+    if len > MAX_LENGTH {
+        return Err(GameNameError::TooLong(len));
+    }
+    if let Some(c) = name.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        return Err(GameNameError::InvalidCharacter(c));
+    }
+    if RESERVED.contains(&name.to_lowercase().as_str()) {
+        return Err(GameNameError::Reserved(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::name::{validate, GameNameError};
+
+    #[test_case("lunch" => Ok(()))]
+    #[test_case("team-7_games" => Ok(()))]
+    #[test_case("ab" => Err(GameNameError::TooShort(2)))]
+    #[test_case("a-very-long-game-name-nobody-wants-to-type" => Err(GameNameError::TooLong(42)))]
+    #[test_case("my game" => Err(GameNameError::InvalidCharacter(' ')))]
+    #[test_case("Admin" => Err(GameNameError::Reserved("Admin".into())))]
+    fn validate_name(name: &str) -> Result<(), GameNameError> {
+        validate(name)
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::game::vote::VotingRule;
+use crate::res::words::WordSelection;
+
+/// Everything chosen when creating a game. Kept with the game so "New Game" starts the same kind of game again.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameOptions {
+    pub words: WordSelection,
+    /// Operatives vote on cards instead of revealing them directly.
+    #[serde(default)]
+    pub voting: Option<VotingRule>,
+}
+
+impl GameOptions {
+    pub fn language(language: &str) -> Self {
+        Self {
+            words: WordSelection::language(language),
+            voting: None,
+        }
+    }
+}
+
+/// Options as stored with a game, games saved before options existed only name their language.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredOptions {
+    Options(GameOptions),
+    Language(String),
+}
+
+/// Reads the options of a stored game, accepting the `language` of older files.
+pub fn stored<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GameOptions, D::Error> {
+    Ok(match StoredOptions::deserialize(deserializer)? {
+        StoredOptions::Options(options) => options,
+        StoredOptions::Language(language) => GameOptions::language(&language),
+    })
+}
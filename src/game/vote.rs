@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+/// When a proposed card gets revealed in voting mode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VotingRule {
+    /// Share of the team's operatives, in percent, that has to vote for the same card.
+    pub majority_percent: u8,
+}
+
+impl VotingRule {
+    /// Votes a card needs with `operatives` eligible voters, at least one.
+    pub fn needed(&self, operatives: usize) -> usize {
+        ((operatives * self.majority_percent as usize + 99) / 100).max(1)
+    }
+}
+
+/// Votes of the team whose turn it is, by voter name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ballot {
+    votes: BTreeMap<String, String>,
+}
+
+impl Ballot {
+    /// Records `voter`'s vote for `word`, replacing an earlier one.
+    pub fn vote(&mut self, voter: &str, word: &str) {
+        self.votes.insert(voter.to_string(), word.to_string());
+    }
+
+    pub fn clear(&mut self) {
+        self.votes.clear();
+    }
+
+    /// Voters per proposed card, only counting the given operatives.
+    pub fn tally(&self, operatives: &[String]) -> BTreeMap<String, Vec<String>> {
+        let mut tally: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (voter, word) in &self.votes {
+            if operatives.contains(voter) {
+                tally.entry(word.clone()).or_insert_with(Vec::new).push(voter.clone());
+            }
+        }
+        tally
+    }
+
+    /// The card enough operatives agree on, if any.
+    pub fn decided(&self, operatives: &[String], rule: &VotingRule) -> Option<String> {
+        let needed = rule.needed(operatives.len());
+        self.tally(operatives)
+            .into_iter()
+            .find(|(_, voters)| voters.len() >= needed)
+            .map(|(word, _)| word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::vote::{Ballot, VotingRule};
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test_case(51, 1 => 1)]
+    #[test_case(51, 2 => 2)]
+    #[test_case(51, 3 => 2)]
+    #[test_case(100, 3 => 3)]
+    #[test_case(0, 3 => 1)]
+    fn votes_needed(percent: u8, operatives: usize) -> usize {
+        VotingRule { majority_percent: percent }.needed(operatives)
+    }
+
+    #[test]
+    fn majority_decides() {
+        let rule = VotingRule { majority_percent: 51 };
+        let operatives = names(&["ann", "bob", "eve"]);
+        let mut ballot = Ballot::default();
+        ballot.vote("ann", "boat");
+        assert_eq!(None, ballot.decided(&operatives, &rule));
+        ballot.vote("bob", "house");
+        ballot.vote("bob", "boat");
+        assert_eq!(Some("boat".to_string()), ballot.decided(&operatives, &rule));
+    }
+
+    #[test]
+    fn only_operatives_count() {
+        let rule = VotingRule { majority_percent: 51 };
+        let mut ballot = Ballot::default();
+        ballot.vote("ann", "boat");
+        ballot.vote("left", "boat");
+        assert_eq!(None, ballot.decided(&names(&["ann", "bob", "eve"]), &rule));
+        assert_eq!(vec![("boat".to_string(), names(&["ann"]))], ballot.tally(&names(&["ann", "bob"])).into_iter().collect::<Vec<_>>());
+    }
+}
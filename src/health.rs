@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static SOCKET_LISTENING: AtomicBool = AtomicBool::new(false);
+static HTTP_LAUNCHED: AtomicBool = AtomicBool::new(false);
+
+/// Marks the socket server as listening for as long as it is alive, including when its thread panics.
+pub struct SocketListening;
+
+impl SocketListening {
+    pub fn new() -> Self {
+        SOCKET_LISTENING.store(true, Ordering::SeqCst);
+        SocketListening
+    }
+}
+
+impl Drop for SocketListening {
+    fn drop(&mut self) {
+        SOCKET_LISTENING.store(false, Ordering::SeqCst);
+    }
+}
+
+pub fn http_launched() {
+    HTTP_LAUNCHED.store(true, Ordering::SeqCst);
+}
+
+pub struct Check {
+    pub name: &'static str,
+    pub result: Result<(), String>,
+}
+
+impl Check {
+    fn new(name: &'static str, ok: bool, problem: &str) -> Self {
+        Self {
+            name,
+            result: if ok { Ok(()) } else { Err(problem.to_string()) },
+        }
+    }
+}
+
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+/// Whether the process is still able to serve games at all. Failing this should get the process restarted.
+pub fn liveness() -> Report {
+    Report {
+        checks: vec![
+            Check::new("http", HTTP_LAUNCHED.load(Ordering::SeqCst), "http server not launched"),
+            Check::new("socket", SOCKET_LISTENING.load(Ordering::SeqCst), "socket server not listening"),
+        ],
+    }
+}
+
+/// Whether the process should receive traffic right now.
+pub fn readiness(cache_lock_timeout: Duration) -> Report {
+    let mut report = liveness();
+    report.checks.push(Check::new(
+        "cache",
+        crate::try_lock_game_cache(cache_lock_timeout).is_some(),
+        "game cache lock not acquirable in time",
+    ));
+    report.checks.push(Check::new(
+        "words",
+        !crate::res::words::languages().is_empty(),
+        "no word packs loaded",
+    ));
+    report.checks.push(Check::new(
+        "shutdown",
+        !crate::shutdown::in_progress(),
+        "shutting down",
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::{Check, Report};
+
+    #[test]
+    fn report_fails_with_single_failed_check() {
+        let report = Report {
+            checks: vec![
+                Check::new("a", true, "a failed"),
+                Check::new("b", false, "b failed"),
+            ],
+        };
+        assert!(!report.is_ok());
+        assert_eq!(Err("b failed".to_string()), report.checks[1].result);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::conf::Rate;
+
+/// Headers proxies pass the client's address in, the first one a request has is used.
+pub const FORWARDING_HEADERS: [&str; 3] = ["Forwarded", "X-Forwarded-For", "X-Real-IP"];
+
+/// How often idle buckets and old strikes are dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref LIMITS: Mutex<AddressLimits> = Mutex::new(AddressLimits::new(Instant::now()));
+}
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket.
+    pub fn new(rate: &Rate, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst as f64,
+            updated: now,
+        }
+    }
+
+    /// Takes `cost` tokens if there are enough.
+    pub fn allow(&mut self, rate: &Rate, now: Instant, cost: u32) -> bool {
+        self.refill(rate, now);
+        if self.tokens >= cost as f64 {
+            self.tokens -= cost as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&mut self, rate: &Rate, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_minute as f64 / 60.0).min(rate.burst as f64);
+        self.updated = now;
+    }
+
+    fn full(&mut self, rate: &Rate, now: Instant) -> bool {
+        self.refill(rate, now);
+        self.tokens >= rate.burst as f64
+    }
+}
+
+/// One token bucket per key, created full on first use.
+#[derive(Debug)]
+struct KeyedLimiter<K> {
+    buckets: HashMap<K, TokenBucket>,
+}
+
+impl<K: Hash + Eq> KeyedLimiter<K> {
+    fn new() -> Self {
+        Self {
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn allow(&mut self, key: K, rate: &Rate, now: Instant, cost: u32) -> bool {
+        self.buckets.entry(key)
+            .or_insert_with(|| TokenBucket::new(rate, now))
+            .allow(rate, now, cost)
+    }
+
+    /// Forgets full buckets, they behave like new ones.
+    fn prune(&mut self, rate: &Rate, now: Instant) {
+        self.buckets.retain(|_, bucket| !bucket.full(rate, now));
+    }
+}
+
+/// Counts misbehaviour per key and bans keys that misbehave too often.
+#[derive(Debug)]
+struct Strikes<K> {
+    strikes: HashMap<K, Vec<Instant>>,
+    banned: HashMap<K, Instant>,
+}
+
+impl<K: Hash + Eq + Clone> Strikes<K> {
+    fn new() -> Self {
+        Self {
+            strikes: HashMap::new(),
+            banned: HashMap::new(),
+        }
+    }
+
+    /// Records a strike and returns whether the key is banned now.
+    fn strike(&mut self, key: K, now: Instant, max: usize, window: Duration, ban: Duration) -> bool {
+        let strikes = self.strikes.entry(key.clone()).or_insert_with(Vec::new);
+        strikes.retain(|strike| now.saturating_duration_since(*strike) < window);
+        strikes.push(now);
+        if strikes.len() >= max {
+            self.strikes.remove(&key);
+            self.banned.insert(key, now + ban);
+            true
+        } else {
+            self.banned(&key, now)
+        }
+    }
+
+    fn banned(&self, key: &K, now: Instant) -> bool {
+        self.banned.get(key).map(|until| now < *until).unwrap_or(false)
+    }
+
+    fn prune(&mut self, now: Instant, window: Duration) {
+        self.banned.retain(|_, until| now < *until);
+        self.strikes.retain(|_, strikes| strikes.iter().any(|strike| now.saturating_duration_since(*strike) < window));
+    }
+}
+
+/// Limits shared by all connections and requests from one address.
+struct AddressLimits {
+    steps: KeyedLimiter<IpAddr>,
+    games: KeyedLimiter<IpAddr>,
+    parse_errors: Strikes<IpAddr>,
+    pruned: Instant,
+}
+
+impl AddressLimits {
+    fn new(now: Instant) -> Self {
+        Self {
+            steps: KeyedLimiter::new(),
+            games: KeyedLimiter::new(),
+            parse_errors: Strikes::new(),
+            pruned: now,
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.pruned) < PRUNE_INTERVAL {
+            return;
+        }
+        let settings = &crate::conf::settings().limits;
+        self.steps.prune(&settings.ip_steps, now);
+        self.games.prune(&settings.ip_games, now);
+        self.parse_errors.prune(now, settings.parse_error_window);
+        self.pruned = now;
+    }
+}
+
+fn with_limits<T, F: FnOnce(&mut AddressLimits, Instant) -> T>(f: F) -> T {
+    let now = Instant::now();
+    let mut limits = LIMITS.lock().unwrap();
+    limits.prune(now);
+    f(&mut limits, now)
+}
+
+/// Takes `cost` socket steps from the address' budget.
+pub fn allow_steps(ip: IpAddr, cost: u32) -> bool {
+    let rate = &crate::conf::settings().limits.ip_steps;
+    with_limits(|limits, now| limits.steps.allow(ip, rate, now, cost))
+}
+
+pub fn allow_game(ip: IpAddr) -> bool {
+    let rate = &crate::conf::settings().limits.ip_games;
+    with_limits(|limits, now| limits.games.allow(ip, rate, now, 1))
+}
+
+/// Records an unparsable message from the address and returns whether it is banned now.
+pub fn parse_error(ip: IpAddr) -> bool {
+    let settings = &crate::conf::settings().limits;
+    with_limits(|limits, now| {
+        limits.parse_errors.strike(ip, now, settings.parse_errors, settings.parse_error_window, settings.ban)
+    })
+}
+
+pub fn banned(ip: IpAddr) -> bool {
+    with_limits(|limits, now| limits.parse_errors.banned(&ip, now))
+}
+
+/// The address limits apply to: the peer, unless it is a `trusted` proxy that `forwarded` the request.
+/// `forwarded` is the name and value of the first of the `FORWARDING_HEADERS` the request has.
+/// Walking the chain of proxies backwards, the client is the first address that is not a trusted proxy.
+pub fn client_address(peer: IpAddr, forwarded: Option<(&str, &str)>, trusted: &[IpAddr]) -> IpAddr {
+    let (name, value) = match forwarded {
+        Some(header) if trusted.contains(&peer) => header,
+        _ => return peer,
+    };
+    let hops: Vec<&str> = if name.eq_ignore_ascii_case("Forwarded") {
+        value.split(',')
+            .flat_map(|element| element.split(';'))
+            .filter_map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(node)) if key.trim().eq_ignore_ascii_case("for") => Some(node),
+                    _ => None,
+                }
+            })
+            .collect()
+    } else {
+        value.split(',').collect()
+    };
+    hops.into_iter()
+        .rev()
+        .map(node_address)
+        .take_while(Option::is_some)
+        .filter_map(|address| address)
+        .find(|address| !trusted.contains(address))
+        .unwrap_or(peer)
+}
+
+/// An address as proxies write it, possibly quoted, in brackets or with a port.
+fn node_address(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>().ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|address| address.ip()))
+        .or_else(|| node.trim_start_matches('[').trim_end_matches(']').parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    use crate::conf::Rate;
+    use crate::limit::{client_address, KeyedLimiter, Strikes, TokenBucket};
+
+    const RATE: Rate = Rate {
+        burst: 3,
+        per_minute: 60,
+    };
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(&RATE, now);
+        assert!(bucket.allow(&RATE, now, 2));
+        assert!(bucket.allow(&RATE, now, 1));
+        assert!(!bucket.allow(&RATE, now, 1));
+        assert!(bucket.allow(&RATE, now + Duration::from_secs(1), 1));
+        assert!(!bucket.allow(&RATE, now + Duration::from_secs(1), 1));
+        assert!(bucket.allow(&RATE, now + Duration::from_secs(60), 3));
+    }
+
+    #[test]
+    fn keys_have_separate_buckets() {
+        let now = Instant::now();
+        let mut limiter = KeyedLimiter::new();
+        assert!(limiter.allow("a", &RATE, now, 3));
+        assert!(!limiter.allow("a", &RATE, now, 1));
+        assert!(limiter.allow("b", &RATE, now, 1));
+        limiter.prune(&RATE, now + Duration::from_secs(3));
+        assert!(limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn repeated_strikes_ban_for_a_while() {
+        let now = Instant::now();
+        let window = Duration::from_secs(10);
+        let ban = Duration::from_secs(60);
+        let mut strikes = Strikes::new();
+        assert!(!strikes.strike("a", now, 3, window, ban));
+        assert!(!strikes.strike("a", now + Duration::from_secs(11), 3, window, ban));
+        assert!(!strikes.strike("a", now + Duration::from_secs(12), 3, window, ban));
+        assert!(strikes.strike("a", now + Duration::from_secs(13), 3, window, ban));
+        assert!(strikes.banned(&"a", now + Duration::from_secs(72)));
+        assert!(!strikes.banned(&"a", now + Duration::from_secs(73)));
+        assert!(!strikes.banned(&"b", now));
+    }
+
+    #[test_case("10.0.0.1", None => "10.0.0.1" ; "direct")]
+    #[test_case("203.0.113.9", Some(("X-Forwarded-For", "1.2.3.4")) => "203.0.113.9" ; "spoofed by untrusted peer")]
+    #[test_case("10.0.0.1", Some(("X-Real-IP", "1.2.3.4")) => "1.2.3.4" ; "real ip from proxy")]
+    #[test_case("10.0.0.1", Some(("X-Forwarded-For", "6.6.6.6, 1.2.3.4")) => "1.2.3.4" ; "spoofed entry before proxy")]
+    #[test_case("10.0.0.1", Some(("X-Forwarded-For", "1.2.3.4, 10.0.0.2")) => "1.2.3.4" ; "chain of trusted proxies")]
+    #[test_case("10.0.0.1", Some(("X-Forwarded-For", "unknown")) => "10.0.0.1" ; "unparsable")]
+    #[test_case("10.0.0.1", Some(("Forwarded", "for=6.6.6.6, for=\"[2001:db8::1]:4711\";proto=https")) => "2001:db8::1" ; "forwarded")]
+    fn client_behind_proxies(peer: &str, forwarded: Option<(&str, &str)>) -> String {
+        let trusted: Vec<IpAddr> = vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+        client_address(peer.parse().unwrap(), forwarded, &trusted).to_string()
+    }
+}
@@ -0,0 +1,168 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value};
+
+use crate::conf::{LogFormat, LogSettings};
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<(&'static str, String)>> = RefCell::new(Vec::new());
+}
+
+/// Installs the logger. Has to happen before the web server starts, which would otherwise install its own.
+pub fn init(settings: &LogSettings) {
+    let logger = Logger {
+        level: settings.level,
+        filters: settings.filters.clone(),
+        format: settings.format.clone(),
+    };
+    let max_level = logger.filters.iter()
+        .map(|(_, level)| *level)
+        .fold(logger.level, std::cmp::max);
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Runs `f` with additional fields attached to every log record written on this thread.
+pub fn with_context<T, F>(fields: &[(&'static str, String)], f: F) -> T where F: FnOnce() -> T {
+    let pushed = push_context(fields);
+    let result = f();
+    pop_context(pushed);
+    result
+}
+
+/// Attaches fields to every following log record on this thread until they are popped again.
+/// Returns the number of fields pushed, to be passed to `pop_context`.
+pub fn push_context(fields: &[(&'static str, String)]) -> usize {
+    CONTEXT.with(|context| context.borrow_mut().extend(fields.iter().cloned()));
+    fields.len()
+}
+
+pub fn pop_context(count: usize) {
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        let len = context.len().saturating_sub(count);
+        context.truncate(len);
+    });
+}
+
+struct Logger {
+    level: LevelFilter,
+    filters: Vec<(String, LevelFilter)>,
+    format: LogFormat,
+}
+
+impl Logger {
+    /// The level for the most specific filter matching the target, or the default level.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.filters.iter()
+            .filter(|(module, _)| target == module || target.starts_with(&format!("{}::", module)))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| format!("{}.{:03}", d.as_secs(), d.subsec_millis()))
+            .unwrap_or_default();
+        let context = CONTEXT.with(|context| context.borrow().clone());
+        match self.format {
+            LogFormat::Text => {
+                let mut line = format!("{} {:<5} {}: {}", timestamp, record.level(), record.target(), record.args());
+                for (key, value) in context {
+                    line.push_str(&format!(" {}={}", key, value));
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut map = Map::new();
+                map.insert("ts".into(), Value::String(timestamp));
+                map.insert("level".into(), Value::String(record.level().to_string()));
+                map.insert("target".into(), Value::String(record.target().into()));
+                map.insert("msg".into(), Value::String(record.args().to_string()));
+                for (key, value) in context {
+                    map.insert(key.into(), Value::String(value));
+                }
+                Value::Object(map).to_string()
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = self.format(record);
+            let _ = writeln!(std::io::stderr(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, Record};
+    use serde_json::Value;
+
+    use crate::conf::LogFormat;
+    use crate::logging::{with_context, Logger};
+
+    fn logger(format: LogFormat) -> Logger {
+        Logger {
+            level: LevelFilter::Info,
+            filters: vec![
+                ("codenamer::web".into(), LevelFilter::Warn),
+                ("codenamer::web::socket".into(), LevelFilter::Debug),
+            ],
+            format,
+        }
+    }
+
+    #[test_case("codenamer::game" => LevelFilter::Info)]
+    #[test_case("codenamer::web" => LevelFilter::Warn)]
+    #[test_case("codenamer::web::language" => LevelFilter::Warn)]
+    #[test_case("codenamer::web::socket" => LevelFilter::Debug)]
+    #[test_case("codenamer::website" => LevelFilter::Info)]
+    fn most_specific_filter_wins(target: &str) -> LevelFilter {
+        logger(LogFormat::Text).level_for(target)
+    }
+
+    #[test]
+    fn json_contains_context() {
+        let logger = logger(LogFormat::Json);
+        let line = with_context(&[("game", "lunch".to_string())], || {
+            logger.format(&Record::builder()
+                .args(format_args!("revealed"))
+                .level(Level::Info)
+                .target("codenamer::game")
+                .build())
+        });
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!("revealed", value["msg"]);
+        assert_eq!("lunch", value["game"]);
+        assert_eq!("INFO", value["level"]);
+    }
+
+    #[test]
+    fn context_is_removed_afterwards() {
+        let logger = logger(LogFormat::Text);
+        with_context(&[("game", "lunch".to_string())], || ());
+        let line = logger.format(&Record::builder()
+            .args(format_args!("idle"))
+            .level(Level::Info)
+            .target("codenamer")
+            .build());
+        assert!(!line.contains("game=lunch"));
+    }
+}
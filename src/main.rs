@@ -1,12 +1,20 @@
 #![feature(decl_macro, proc_macro_hygiene)]
 
 extern crate askama;
+extern crate clap;
 extern crate config;
 extern crate colored;
+extern crate crossterm;
+extern crate ctrlc;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate log;
+#[cfg(test)]
+extern crate proptest;
 extern crate rand;
+extern crate rmp_serde;
 #[macro_use]
 extern crate rocket;
 extern crate serde;
@@ -16,22 +24,37 @@ extern crate serde_json;
 #[cfg(test)]
 #[macro_use]
 extern crate test_case;
+extern crate unicode_normalization;
+extern crate url;
 extern crate uuid;
 extern crate ws;
 
-use std::sync::{Arc, Mutex};
-use std::thread::{sleep, spawn};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
+use crate::conf::Settings;
+use crate::supervisor::{supervise, Policy};
 use crate::game::cache::{GameSessionCache, RamGameCache};
-use std::collections::HashMap;
 
+pub mod bot;
+pub mod cli;
+pub mod client;
 pub mod conf;
 pub mod game;
-#[cfg(debug)]
+pub mod health;
+pub mod limit;
+pub mod logging;
+pub mod metrics;
+pub mod play;
 pub mod print;
+pub mod protocol;
 pub mod random;
 pub mod res;
+pub mod shutdown;
+pub mod similarity;
+pub mod supervisor;
+pub mod tui;
 mod web;
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -42,41 +65,82 @@ lazy_static! {
     };
 }
 
-pub fn game_cache() -> Arc<Mutex<Box<dyn GameSessionCache + Send>>> {
-    GAME_CACHE.clone()
+/// Tries to lock the game cache until `timeout` has passed.
+pub fn try_lock_game_cache(timeout: Duration) -> Option<MutexGuard<'static, Box<dyn GameSessionCache + Send>>> {
+    let start = Instant::now();
+    loop {
+        match GAME_CACHE.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(_)) => return None,
+            Err(TryLockError::WouldBlock) if start.elapsed() >= timeout => return None,
+            Err(TryLockError::WouldBlock) => sleep(Duration::from_millis(10)),
+        }
+    }
 }
 
-fn one_day() -> Duration {
-    Duration::from_secs(24 * 60 * 60 * 1000)
+/// Locks the game cache, recording how long it took to acquire the lock.
+pub fn lock_game_cache() -> MutexGuard<'static, Box<dyn GameSessionCache + Send>> {
+    let start = Instant::now();
+    let guard = GAME_CACHE.lock().unwrap();
+    metrics::METRICS.cache_lock_wait.observe(start.elapsed());
+    guard
 }
 
 fn main() {
-    println!("Codenamer server v{}!", VERSION);
-    println!("Languages: {}", res::words::languages().len());
-    let conf = conf::CONFIG.clone();
-    println!("{:?}",
-             conf.try_into::<HashMap<String, String>>().unwrap());
+    let matches = cli::app().get_matches();
+    let settings = conf::settings();
+    logging::init(&settings.log);
+    if matches.is_present("print-config") {
+        print!("{}", settings);
+        return;
+    }
+    match matches.subcommand() {
+        ("", _) | ("serve", _) => serve(settings),
+        (command, Some(args)) => std::process::exit(cli::run(command, args, settings)),
+        (command, None) => unreachable!("subcommand {} without arguments", command),
+    }
+}
+
+fn serve(settings: &'static Settings) {
+    info!("Codenamer server v{}!", VERSION);
+    info!("Languages: {}", res::words::languages().len());
+    for (key, value) in settings.entries() {
+        debug!("{} = {:?}", key, value);
+    }
     {
-        let gc = game_cache();
-        let mut cache =gc .lock().unwrap();
-        *cache = Box::new(RamGameCache::new());
+        let mut cache = lock_game_cache();
+        *cache = match game::cache::from_settings(&settings.cache) {
+            Ok(configured) => configured,
+            Err(e) => {
+                error!("Could not open game cache: {:?}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+    shutdown::install(settings.shutdown.deadline);
+    let restart = Policy::Restart {
+        max_restarts: settings.health.max_restarts,
+        window: settings.health.restart_window,
+    };
+    let handles = vec![
+        supervise("http server", Policy::Exit, web::start),
+        supervise("socket server", restart, web::socket::start),
+        supervise("cache cleanup", restart, clean_cache),
+    ];
+    info!("Visit http://127.0.0.1:{}/ to play", settings.http.port);
+    for handle in handles {
+        handle.join().unwrap();
     }
-    let web_handle = spawn(|| {
-        web::start();
-    });
-    let web_socket_handle = spawn(|| {
-        web::socket::start();
-    });
-    println!("Visit http://127.0.0.1:8000/ to play");
-    let clean_cache_handle = spawn(|| {
-        loop {
-            sleep(Duration::from_secs(5));
-            let gc = game_cache();
-            let mut cache = gc.lock().unwrap();
-            cache.cleanup(&one_day());
+}
+
+fn clean_cache() {
+    let settings = conf::settings();
+    loop {
+        sleep(settings.cache.cleanup_interval);
+        let mut cache = lock_game_cache();
+        cache.cleanup(&settings.cache.max_age);
+        if let Err(e) = cache.flush() {
+            error!("Could not flush game cache: {:?}", e);
         }
-    });
-    web_handle.join().unwrap();
-    web_socket_handle.join().unwrap();
-    clean_cache_handle.join().unwrap();
+    }
 }
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static! {
+    pub static ref METRICS: Metrics = Metrics::new();
+}
+
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    fn new() -> Self {
+        Counter(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Gauge(AtomicUsize);
+
+impl Gauge {
+    fn new() -> Self {
+        Gauge(AtomicUsize::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters that are split by a single label, e.g. the step type.
+pub struct LabeledCounter {
+    label: &'static str,
+    values: Mutex<BTreeMap<String, u64>>,
+}
+
+impl LabeledCounter {
+    fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, value: &str) {
+        *self.values.lock().unwrap().entry(value.to_string()).or_insert(0) += 1;
+    }
+}
+
+const LOCK_WAIT_BUCKETS: [f64; 6] = [0.0001, 0.001, 0.01, 0.1, 1.0, 10.0];
+
+pub struct Histogram {
+    buckets: Vec<(f64, AtomicU64)>,
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            buckets: bounds.iter().map(|bound| (*bound, AtomicU64::new(0))).collect(),
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, count) in &self.buckets {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+pub struct Metrics {
+    pub games_created: Counter,
+    pub games_finished: Counter,
+    pub games_expired: Counter,
+    pub socket_connections: Gauge,
+    pub socket_timeouts: Counter,
+    pub rate_limited: LabeledCounter,
+    pub bans: Counter,
+    pub socket_steps: LabeledCounter,
+    pub socket_parse_errors: LabeledCounter,
+    pub reveal_outcomes: LabeledCounter,
+    pub cache_lock_wait: Histogram,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            games_created: Counter::new(),
+            games_finished: Counter::new(),
+            games_expired: Counter::new(),
+            socket_connections: Gauge::new(),
+            socket_timeouts: Counter::new(),
+            rate_limited: LabeledCounter::new("limit"),
+            bans: Counter::new(),
+            socket_steps: LabeledCounter::new("type"),
+            socket_parse_errors: LabeledCounter::new("error"),
+            reveal_outcomes: LabeledCounter::new("team"),
+            cache_lock_wait: Histogram::new(&LOCK_WAIT_BUCKETS),
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self, cached_games: usize) -> String {
+        let mut out = String::new();
+        counter(&mut out, "codenamer_games_created_total", "Games created.", &self.games_created);
+        counter(&mut out, "codenamer_games_finished_total", "Games that ended with a winner.", &self.games_finished);
+        counter(&mut out, "codenamer_games_expired_total", "Games removed from the cache because of their age.", &self.games_expired);
+        header(&mut out, "codenamer_games_cached", "Games currently held in the cache.", "gauge");
+        writeln!(out, "codenamer_games_cached {}", cached_games).unwrap();
+        header(&mut out, "codenamer_socket_connections", "Open WebSocket connections.", "gauge");
+        writeln!(out, "codenamer_socket_connections {}", self.socket_connections.get()).unwrap();
+        counter(&mut out, "codenamer_socket_timeouts_total", "Connections dropped for missing heartbeats.", &self.socket_timeouts);
+        labeled(&mut out, "codenamer_rate_limited_total", "Requests refused by a rate limit, per limit.", &self.rate_limited);
+        counter(&mut out, "codenamer_bans_total", "Addresses banned for sending invalid messages.", &self.bans);
+        labeled(&mut out, "codenamer_socket_steps_total", "Steps received per step type.", &self.socket_steps);
+        labeled(&mut out, "codenamer_socket_parse_errors_total", "Messages that could not be parsed, per error.", &self.socket_parse_errors);
+        labeled(&mut out, "codenamer_reveal_outcomes_total", "Revealed cards per team.", &self.reveal_outcomes);
+        histogram(&mut out, "codenamer_cache_lock_wait_seconds", "Time spent waiting for the game cache lock.", &self.cache_lock_wait);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn counter(out: &mut String, name: &str, help: &str, counter: &Counter) {
+    header(out, name, help, "counter");
+    writeln!(out, "{} {}", name, counter.get()).unwrap();
+}
+
+fn labeled(out: &mut String, name: &str, help: &str, counter: &LabeledCounter) {
+    header(out, name, help, "counter");
+    for (value, count) in counter.values.lock().unwrap().iter() {
+        writeln!(out, "{}{{{}=\"{}\"}} {}", name, counter.label, value, count).unwrap();
+    }
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    header(out, name, help, "histogram");
+    let count = histogram.count.load(Ordering::Relaxed);
+    for (bound, bucket) in &histogram.buckets {
+        writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, bucket.load(Ordering::Relaxed)).unwrap();
+    }
+    writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count).unwrap();
+    let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+    writeln!(out, "{}_sum {}", name, sum).unwrap();
+    writeln!(out, "{}_count {}", name, count).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn render_labeled_counters() {
+        let metrics = Metrics::new();
+        metrics.socket_steps.inc("reveal");
+        metrics.socket_steps.inc("reveal");
+        metrics.socket_steps.inc("skip");
+        let text = metrics.render(0);
+        assert!(text.contains("codenamer_socket_steps_total{type=\"reveal\"} 2\n"));
+        assert!(text.contains("codenamer_socket_steps_total{type=\"skip\"} 1\n"));
+    }
+
+    #[test]
+    fn render_histogram_buckets() {
+        let metrics = Metrics::new();
+        metrics.cache_lock_wait.observe(Duration::from_millis(5));
+        let text = metrics.render(3);
+        assert!(text.contains("codenamer_cache_lock_wait_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(text.contains("codenamer_cache_lock_wait_seconds_bucket{le=\"0.01\"} 1\n"));
+        assert!(text.contains("codenamer_cache_lock_wait_seconds_count 1\n"));
+        assert!(text.contains("codenamer_games_cached 3\n"));
+    }
+}
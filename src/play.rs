@@ -0,0 +1,178 @@
+use std::io::{self, BufRead, Write};
+
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::terminal::{Clear, ClearType};
+
+use crate::game::clue::Clue;
+use crate::game::{Color, Game, RevealOutcome, Team};
+use crate::print::{ColoredDesc, PublicDesc};
+
+/// Plays `game` on one computer: the spymasters take turns looking at the key card, then the board is
+/// cleared and shown to the operatives without it. Stops when the game is won or the input ends.
+pub fn play<R: BufRead, W: Write>(game: &mut Game, input: &mut R, out: &mut W) -> io::Result<()> {
+    while game.winner.is_none() {
+        let team = game.turn.clone();
+        clear(out)?;
+        write!(out, "Pass to the {} spymaster and press enter when nobody else is looking.", team.desc_colored())?;
+        out.flush()?;
+        if read_line(input)?.is_none() {
+            return Ok(());
+        }
+        writeln!(out, "{}", game.desc_colored())?;
+        if !ask_clue(game, input, out)? {
+            return Ok(());
+        }
+        clear(out)?;
+        if !guess(game, &team, input, out)? {
+            return Ok(());
+        }
+    }
+    writeln!(out, "{}", game.desc_colored())?;
+    if let Some(winner) = &game.winner {
+        writeln!(out, "{} wins!", winner.desc_colored())?;
+    }
+    Ok(())
+}
+
+/// Asks the spymaster for a clue until it is valid, returns false if the input ended.
+fn ask_clue<R: BufRead, W: Write>(game: &mut Game, input: &mut R, out: &mut W) -> io::Result<bool> {
+    loop {
+        write!(out, "Clue (word and number): ")?;
+        out.flush()?;
+        let line = match read_line(input)? {
+            Some(line) => line,
+            None => return Ok(false),
+        };
+        match Clue::parse(&line).and_then(|clue| game.give_clue(clue)) {
+            Ok(()) => return Ok(true),
+            Err(e) => writeln!(out, "{}", e)?,
+        }
+    }
+}
+
+/// Lets the operatives of `team` reveal cards for the clue until their turn ends, returns false if the input ended.
+fn guess<R: BufRead, W: Write>(game: &mut Game, team: &Color, input: &mut R, out: &mut W) -> io::Result<bool> {
+    loop {
+        writeln!(out, "{}", game.desc_public())?;
+        let clue = game.clue.as_ref().map(|clue| clue.to_string()).unwrap_or_default();
+        let left = game.guesses_left().map(|left| left.to_string()).unwrap_or_else(|| "any".into());
+        writeln!(out, "Clue for {}: {}, {} guesses left.", team.desc_colored(), clue, left)?;
+        write!(out, "Card to reveal, or 'pass': ")?;
+        out.flush()?;
+        let line = match read_line(input)? {
+            Some(line) => line,
+            None => return Ok(false),
+        };
+        if line.eq_ignore_ascii_case("pass") {
+            game.skip();
+            return Ok(true);
+        }
+        let word = match hidden_card(game, &line) {
+            Some(word) => word,
+            None => {
+                writeln!(out, "There is no hidden card '{}'.", line)?;
+                continue;
+            }
+        };
+        if let RevealOutcome::Opened(word, card) = game.reveal(&word) {
+            writeln!(out, "{} belongs to {}.", word, team_desc(&card))?;
+        }
+        if game.winner.is_some() || game.turn.ne(team) {
+            return Ok(true);
+        }
+    }
+}
+
+/// The hidden card matching `word`, ignoring case.
+fn hidden_card(game: &Game, word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    game.words.iter()
+        .find(|w| !w.opened && w.word.to_lowercase() == word)
+        .map(|w| w.word.clone())
+}
+
+fn team_desc(team: &Team) -> String {
+    match team {
+        Team::Player(color) => format!("team {}", color.desc_colored()),
+        Team::None => "nobody".into(),
+        Team::Death => "the assassin".into(),
+    }
+}
+
+/// The next line without surrounding whitespace, `None` at the end of the input.
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn clear<W: Write>(out: &mut W) -> io::Result<()> {
+    queue!(out, Clear(ClearType::All), MoveTo(0, 0)).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::game::options::GameOptions;
+    use crate::game::{Color, Game, Team};
+    use crate::play::play;
+
+    fn game() -> Game {
+        let mut game = Game::with_rng("local".into(), GameOptions::language("german"), &mut StdRng::seed_from_u64(1)).unwrap();
+        for (i, w) in game.words.iter_mut().enumerate() {
+            w.word = format!("card{:02}", i);
+        }
+        game
+    }
+
+    fn card(game: &Game, team: Team) -> String {
+        game.words.iter().find(|w| w.team == team).unwrap().word.clone()
+    }
+
+    fn run(game: &mut Game, input: &str) -> String {
+        let mut out = vec![];
+        play(game, &mut Cursor::new(input.as_bytes()), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn assassin_ends_the_game() {
+        let mut game = game();
+        let input = format!("\nocean 1\n{}\n", card(&game, Team::Death).to_uppercase());
+        let out = run(&mut game, &input);
+        assert_eq!(Some(Color::Blue), game.winner);
+        assert!(out.contains("the assassin"));
+        assert!(out.contains("wins!"));
+    }
+
+    #[test]
+    fn invalid_clues_are_asked_again() {
+        let mut game = game();
+        let input = format!("\nocean\n{} 2\nocean 1\npass\n", card(&game, Team::None));
+        let out = run(&mut game, &input);
+        assert!(out.contains("Expected one word and a number"));
+        assert!(out.contains("The clue may not name the card"));
+        assert!(out.contains("Clue for"));
+        assert_eq!(Color::Blue, game.turn);
+    }
+
+    #[test]
+    fn turn_ends_after_count_plus_one_guesses() {
+        let mut game = game();
+        let red: Vec<String> = game.words.iter()
+            .filter(|w| w.team == Team::Player(Color::Red))
+            .map(|w| w.word.clone())
+            .collect();
+        let input = format!("\nocean 1\n{}\n{}\n", red[0], red[1]);
+        run(&mut game, &input);
+        assert_eq!(2, game.words.iter().filter(|w| w.opened).count());
+        assert_eq!(Color::Blue, game.turn);
+    }
+}
@@ -6,6 +6,11 @@ pub trait ColoredDesc {
     fn desc_colored(&self) -> String;
 }
 
+/// Like `ColoredDesc`, but only shows the teams of revealed cards, as everyone at the table sees them.
+pub trait PublicDesc {
+    fn desc_public(&self) -> String;
+}
+
 const GAME_DESC_DELIMITER: &'static str = "==================";
 
 impl ColoredDesc for Color {
@@ -19,30 +24,39 @@ impl ColoredDesc for Color {
 
 impl ColoredDesc for Game {
     fn desc_colored(&self) -> String {
-        let mut field = String::new();
-        for y in 0..5 {
-            let mut line = String::new();
-            for x in 0..5 {
-                let word = self.words.get(x + y * 5).unwrap();
-                println!("Word: {}, desc: '{}'", word.word, word.desc_colored());
-                line = format!("{} {}", line, word.desc_colored());
-            }
-            field = format!("{}\n{}", field, line);
+        describe_game(self, ColoredDesc::desc_colored)
+    }
+}
+
+impl PublicDesc for Game {
+    fn desc_public(&self) -> String {
+        describe_game(self, PublicDesc::desc_public)
+    }
+}
+
+fn describe_game(game: &Game, describe_word: fn(&GameWord) -> String) -> String {
+    let mut field = String::new();
+    for y in 0..5 {
+        let mut line = String::new();
+        for x in 0..5 {
+            let word = game.words.get(x + y * 5).unwrap();
+            line = format!("{} {}", line, describe_word(word));
         }
-        let is_won = self.winner.is_some();
-        let turn = if is_won { "winner" } else { "turn" };
-        let turn_team = if is_won { self.winner.clone().unwrap() } else { self.turn.clone() };
-        format!(
-            "{}\nGame: {}\nIdent: {}\n{}: {}\n{}\n{}",
-            GAME_DESC_DELIMITER,
-            &self.name,
-            &self.ident,
-            turn,
-            turn_team.desc_colored(),
-            field,
-            GAME_DESC_DELIMITER
-        )
+        field = format!("{}\n{}", field, line);
     }
+    let is_won = game.winner.is_some();
+    let turn = if is_won { "winner" } else { "turn" };
+    let turn_team = if is_won { game.winner.clone().unwrap() } else { game.turn.clone() };
+    format!(
+        "{}\nGame: {}\nIdent: {}\n{}: {}\n{}\n{}",
+        GAME_DESC_DELIMITER,
+        &game.name,
+        &game.ident,
+        turn,
+        turn_team.desc_colored(),
+        field,
+        GAME_DESC_DELIMITER
+    )
 }
 
 impl ColoredDesc for GameWord {
@@ -53,6 +67,16 @@ impl ColoredDesc for GameWord {
     }
 }
 
+impl PublicDesc for GameWord {
+    fn desc_public(&self) -> String {
+        if self.opened {
+            self.desc_colored()
+        } else {
+            format!("-{:^10}", self.word)
+        }
+    }
+}
+
 fn color_for_team(text: &str, team: &Team) -> String {
     use Color::*;
     use Team::*;
@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use serde::export::Formatter;
+use serde_json::Value;
+use ws::Message;
+
+/// Subprotocol a client asks for in `Sec-WebSocket-Protocol` to receive MessagePack.
+pub const MESSAGE_PACK_PROTOCOL: &str = "codenamer.msgpack";
+
+/// How the server encodes what it sends to a client. The message structure is the same in every encoding.
+/// Incoming frames are decoded by their type: text frames are JSON, binary frames MessagePack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    /// The encoding for the subprotocols a client offered, JSON unless it asked for MessagePack.
+    pub fn negotiate(protocols: &[&str]) -> Encoding {
+        if protocols.contains(&MESSAGE_PACK_PROTOCOL) {
+            Encoding::MessagePack
+        } else {
+            Encoding::Json
+        }
+    }
+
+    /// The subprotocol to confirm in the handshake response, if any.
+    pub fn protocol(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Json => None,
+            Encoding::MessagePack => Some(MESSAGE_PACK_PROTOCOL),
+        }
+    }
+
+    pub fn encode(&self, value: &Value) -> Message {
+        match self {
+            Encoding::Json => Message::Text(serde_json::to_string(value).unwrap()),
+            Encoding::MessagePack => Message::Binary(rmp_serde::to_vec_named(value).unwrap()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::decode::Error),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Json(e) => write!(f, "invalid json: {}", e),
+            DecodeError::MessagePack(e) => write!(f, "invalid message pack: {}", e),
+        }
+    }
+}
+
+/// Decodes a frame according to its type.
+pub fn decode(message: &Message) -> Result<Value, DecodeError> {
+    match message {
+        Message::Text(text) => serde_json::from_str(text).map_err(DecodeError::Json),
+        Message::Binary(data) => rmp_serde::from_slice(data).map_err(DecodeError::MessagePack),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::protocol::codec::{decode, Encoding, MESSAGE_PACK_PROTOCOL};
+
+    #[test_case(&[] => Encoding::Json)]
+    #[test_case(&["chat", MESSAGE_PACK_PROTOCOL] => Encoding::MessagePack)]
+    fn negotiate(protocols: &[&str]) -> Encoding {
+        Encoding::negotiate(protocols)
+    }
+
+    #[test_case(Encoding::Json)]
+    #[test_case(Encoding::MessagePack)]
+    fn round_trip(encoding: Encoding) {
+        let value: Value = serde_json::from_str(r#"{"game": "Abc", "seq": 3, "steps": [{"type": "reveal", "word": "boat"}]}"#).unwrap();
+        assert_eq!(value, decode(&encoding.encode(&value)).unwrap());
+    }
+
+    #[test]
+    fn message_pack_is_smaller() {
+        let value: Value = serde_json::from_str(r#"{"game": "Abc", "seq": 300, "steps": [{"type": "state", "spectators": 12}]}"#).unwrap();
+        assert!(Encoding::MessagePack.encode(&value).len() < Encoding::Json.encode(&value).len());
+    }
+}
@@ -0,0 +1,864 @@
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+use serde::export::Formatter;
+use serde_json::{Map, Value};
+use ws::Message;
+
+use crate::game::chat::Channel;
+use crate::game::clue::Clue;
+use crate::game::{Color, Team};
+use crate::protocol::codec::DecodeError;
+
+pub mod codec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Player,
+    /// Receives every update but may not change the game.
+    Spectator,
+}
+
+impl Role {
+    pub fn parse(value: &str) -> Option<Role> {
+        match value {
+            "player" => Some(Role::Player),
+            "spectator" => Some(Role::Spectator),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Role::Player => "player",
+            Role::Spectator => "spectator",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum MsgParseError {
+    TooLarge,
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::decode::Error),
+    InvalidJsonStructure,
+    TypeError,
+}
+
+impl MsgParseError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MsgParseError::TooLarge => "too_large",
+            MsgParseError::Json(_) => "json",
+            MsgParseError::MessagePack(_) => "message_pack",
+            MsgParseError::InvalidJsonStructure => "invalid_json_structure",
+            MsgParseError::TypeError => "type_error",
+        }
+    }
+}
+
+impl Display for MsgParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MsgParseError::TooLarge => write!(f, "message too large"),
+            MsgParseError::Json(e) => write!(f, "invalid json: {}", e),
+            MsgParseError::MessagePack(e) => write!(f, "invalid message pack: {}", e),
+            MsgParseError::InvalidJsonStructure => write!(f, "unexpected message structure"),
+            MsgParseError::TypeError => write!(f, "unknown step type"),
+        }
+    }
+}
+
+impl From<serde_json::Error> for MsgParseError {
+    fn from(e: serde_json::Error) -> MsgParseError {
+        MsgParseError::Json(e)
+    }
+}
+
+impl From<DecodeError> for MsgParseError {
+    fn from(e: DecodeError) -> MsgParseError {
+        match e {
+            DecodeError::Json(e) => MsgParseError::Json(e),
+            DecodeError::MessagePack(e) => MsgParseError::MessagePack(e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reveal {
+    pub word: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Vote {
+    pub word: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Join {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub captain: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Chat {
+    pub channel: Channel,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Join(Join),
+    Chat(Chat),
+    Vote(Vote),
+    Reveal(Reveal),
+    Reset(Reset),
+    Skip,
+    Spy,
+    Clue(Clue),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reset;
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Join(_) => "join",
+            Step::Chat(_) => "chat",
+            Step::Vote(_) => "vote",
+            Step::Reveal(_) => "reveal",
+            Step::Reset(_) => "reset",
+            Step::Skip => "skip",
+            Step::Spy => "spy",
+            Step::Clue(_) => "clue",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Msg {
+    pub game: String,
+    pub ident: String,
+    pub role: Role,
+    /// Session to resume, from an earlier connection of this client.
+    pub session: Option<String>,
+    /// The last event the client received in that session.
+    pub seq: Option<u64>,
+    pub steps: Vec<Step>,
+}
+
+impl TryFrom<&String> for Msg {
+    type Error = MsgParseError;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        let parsed: Value = serde_json::from_str(&value)?;
+        Msg::try_from(parsed)
+    }
+}
+
+impl TryFrom<Value> for Msg {
+    type Error = MsgParseError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        trace!("client message: {}", value);
+        match value {
+            Value::Object(obj) => {
+                let game = game_name(&obj)?;
+                let ident = ident(&obj)?;
+                let role = role(&obj)?;
+                let session = session(&obj)?;
+                let seq = seq(&obj)?;
+                let steps = steps(&obj)?;
+                Ok(Self {
+                    game,
+                    ident,
+                    role,
+                    session,
+                    seq,
+                    steps,
+                })
+            }
+            _ => Err(MsgParseError::InvalidJsonStructure),
+        }
+    }
+}
+
+impl TryFrom<Message> for Msg {
+    type Error = MsgParseError;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        Msg::try_from(codec::decode(&value)?)
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Step {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        match value.get("type") {
+            Some(Value::String(step_type)) => {
+                match step_type.as_str() {
+                    "join" => Ok(Step::Join(Join::try_from(value)?)),
+                    "chat" => Ok(Step::Chat(Chat::try_from(value)?)),
+                    "vote" => Ok(Step::Vote(Vote::try_from(value)?)),
+                    "reveal" => Ok(Step::Reveal(Reveal::try_from(value)?)),
+                    "reset" => Ok(Step::Reset(Reset::try_from(value)?)),
+                    "skip" => Ok(Step::Skip),
+                    "spy" => Ok(Step::Spy),
+                    "clue" => Ok(Step::Clue(clue(value)?)),
+                    _ => Err(MsgParseError::TypeError),
+                }
+            }
+            _ => Err(MsgParseError::InvalidJsonStructure),
+        }
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Reveal {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        if let Some(Value::String(word)) = value.get("word") {
+            Ok(Reveal {
+                word: word.clone(),
+            })
+        } else {
+            Err(MsgParseError::InvalidJsonStructure)
+        }
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Join {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        let name = match value.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        let team = match value.get("team") {
+            Some(Value::String(team)) if team == "red" => Color::Red,
+            Some(Value::String(team)) if team == "blue" => Color::Blue,
+            _ => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        Ok(Join {
+            name,
+            team,
+            spymaster: flag(value, "spymaster")?,
+            captain: flag(value, "captain")?,
+        })
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Vote {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        if let Some(Value::String(word)) = value.get("word") {
+            Ok(Vote {
+                word: word.clone(),
+            })
+        } else {
+            Err(MsgParseError::InvalidJsonStructure)
+        }
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Chat {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        let channel = match value.get("channel") {
+            None => Channel::Global,
+            Some(Value::String(channel)) => Channel::parse(channel).ok_or(MsgParseError::InvalidJsonStructure)?,
+            Some(_) => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        match value.get("text") {
+            Some(Value::String(text)) => Ok(Chat {
+                channel,
+                text: text.clone(),
+            }),
+            _ => Err(MsgParseError::InvalidJsonStructure),
+        }
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Reset {
+    type Error = MsgParseError;
+
+    fn try_from(_value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        Ok(Reset)
+    }
+}
+
+fn game_name(obj: &Map<String, Value>) -> Result<String, MsgParseError> {
+    if let Some(Value::String(name)) = obj.get("game") {
+        Ok(name.clone())
+    } else {
+        Err(MsgParseError::InvalidJsonStructure)
+    }
+}
+
+fn ident(obj: &Map<String, Value>) -> Result<String, MsgParseError> {
+    if let Some(Value::String(name)) = obj.get("ident") {
+        Ok(name.clone())
+    } else {
+        Err(MsgParseError::InvalidJsonStructure)
+    }
+}
+
+fn role(obj: &Map<String, Value>) -> Result<Role, MsgParseError> {
+    match obj.get("role") {
+        None => Ok(Role::Player),
+        Some(Value::String(role)) => Role::parse(role).ok_or(MsgParseError::InvalidJsonStructure),
+        Some(_) => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn session(obj: &Map<String, Value>) -> Result<Option<String>, MsgParseError> {
+    match obj.get("session") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(session)) => Ok(Some(session.clone())),
+        Some(_) => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn seq(obj: &Map<String, Value>) -> Result<Option<u64>, MsgParseError> {
+    match obj.get("seq") {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+/// An optional boolean field, false if missing.
+fn flag(obj: &Map<String, Value>, key: &str) -> Result<bool, MsgParseError> {
+    match obj.get(key) {
+        None => Ok(false),
+        Some(Value::Bool(value)) => Ok(*value),
+        Some(_) => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn steps(obj: &Map<String, Value>) -> Result<Vec<Step>, MsgParseError> {
+    if let Some(Value::Array(values)) = obj.get("steps") {
+        let mut steps: Vec<Step> = vec![];
+        for value in values {
+            if let Value::Object(map) = value {
+                steps.push(Step::try_from(map)?);
+            } else {
+                return Err(MsgParseError::InvalidJsonStructure);
+            }
+        }
+        Ok(steps)
+    } else {
+        Err(MsgParseError::InvalidJsonStructure)
+    }
+}
+impl Into<Value> for Step {
+    fn into(self) -> Value {
+        let mut map = Map::new();
+        map.insert("type".into(), Value::String(self.name().into()));
+        match self {
+            Step::Join(j) => {
+                map.insert("name".into(), Value::String(j.name));
+                map.insert("team".into(), Value::String(j.team.to_string()));
+                map.insert("spymaster".into(), Value::Bool(j.spymaster));
+                map.insert("captain".into(), Value::Bool(j.captain));
+            }
+            Step::Chat(c) => {
+                map.insert("channel".into(), Value::String(c.channel.to_string()));
+                map.insert("text".into(), Value::String(c.text));
+            }
+            Step::Vote(v) => {
+                map.insert("word".into(), Value::String(v.word));
+            }
+            Step::Reveal(r) => {
+                map.insert("word".into(), Value::String(r.word));
+            }
+            Step::Clue(c) => {
+                map.insert("word".into(), Value::String(c.word));
+                map.insert("count".into(), Value::from(c.count));
+            }
+            Step::Reset(_) | Step::Skip | Step::Spy => {}
+        }
+        Value::Object(map)
+    }
+}
+
+impl Into<Value> for Msg {
+    fn into(self) -> Value {
+        let mut map = Map::new();
+        map.insert("game".into(), Value::String(self.game));
+        map.insert("ident".into(), Value::String(self.ident));
+        map.insert("role".into(), Value::String(self.role.to_string()));
+        if let Some(session) = self.session {
+            map.insert("session".into(), Value::String(session));
+        }
+        if let Some(seq) = self.seq {
+            map.insert("seq".into(), Value::from(seq));
+        }
+        map.insert("steps".into(), Value::Array(self.steps.into_iter().map(|step| step.into()).collect()));
+        Value::Object(map)
+    }
+}
+
+/// A card as revealed to everyone or as shown to spymasters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Card {
+    pub word: String,
+    pub team: Team,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub captain: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatLine {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub channel: Channel,
+    pub text: String,
+    /// Seconds since the unix epoch.
+    pub sent: u64,
+}
+
+/// Proposed cards with their voters, in voting mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Votes {
+    pub proposals: Vec<(String, Vec<String>)>,
+    pub needed: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    /// All words on the board, row by row.
+    pub words: Vec<String>,
+    pub team: Color,
+    pub winner: Option<Color>,
+    pub revealed: Vec<Card>,
+    pub spectators: usize,
+    pub votes: Option<Votes>,
+    pub clue: Option<Clue>,
+    /// Cards the team may still reveal for the clue, `None` without a limit.
+    pub guesses_left: Option<usize>,
+}
+
+/// What the server tells a client, one event per step of a server message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The session to resume after reconnecting and the latest event of the game.
+    Session { id: String, resumed: bool, seq: u64, ident: Option<String> },
+    State(State),
+    Reveal(Card),
+    Spy(Vec<Card>),
+    Roster(Vec<Player>),
+    /// `history` replaces the messages shown so far instead of adding to them.
+    Chat { messages: Vec<ChatLine>, history: bool },
+    Error(String),
+    /// The game was replaced or the client's ident is outdated.
+    Reload { ident: Option<String> },
+    Turn(Color),
+    Win(Color),
+    Shutdown,
+}
+
+/// A message from the server. Only broadcast events carry a sequence number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerMsg {
+    pub game: Option<String>,
+    pub seq: Option<u64>,
+    pub events: Vec<Event>,
+}
+
+impl TryFrom<Value> for ServerMsg {
+    type Error = MsgParseError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        let game = match obj.get("game") {
+            Some(Value::String(game)) => Some(game.clone()),
+            _ => None,
+        };
+        let steps = match obj.get("steps") {
+            Some(Value::Array(steps)) => steps,
+            _ => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        let mut events = vec![];
+        for step in steps {
+            match step {
+                Value::Object(map) => match Event::try_from(map) {
+                    Ok(event) => events.push(event),
+                    // newer servers may send steps this client does not know yet
+                    Err(MsgParseError::TypeError) => {}
+                    Err(e) => return Err(e),
+                },
+                Value::Null => {}
+                _ => return Err(MsgParseError::InvalidJsonStructure),
+            }
+        }
+        Ok(ServerMsg {
+            game,
+            seq: seq(&obj)?,
+            events,
+        })
+    }
+}
+
+impl TryFrom<Message> for ServerMsg {
+    type Error = MsgParseError;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        ServerMsg::try_from(codec::decode(&value)?)
+    }
+}
+
+impl TryFrom<&Map<String, Value>> for Event {
+    type Error = MsgParseError;
+
+    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
+        let step_type = match value.get("type") {
+            Some(Value::String(step_type)) => step_type.as_str(),
+            _ => return Err(MsgParseError::InvalidJsonStructure),
+        };
+        match step_type {
+            "session" => Ok(Event::Session {
+                id: text(value, "id")?,
+                resumed: flag(value, "resumed")?,
+                seq: seq(value)?.unwrap_or(0),
+                ident: optional_text(value, "ident")?,
+            }),
+            "state" => Ok(Event::State(State {
+                words: texts(value, "words")?,
+                team: color(value, "team")?,
+                winner: match value.get("winner") {
+                    None => None,
+                    Some(_) => Some(color(value, "winner")?),
+                },
+                revealed: objects(value, "revealed")?.into_iter().map(card).collect::<Result<_, _>>()?,
+                spectators: number(value, "spectators")? as usize,
+                votes: match value.get("votes") {
+                    None => None,
+                    Some(_) => Some(Votes {
+                        proposals: objects(value, "votes")?.into_iter().map(proposal).collect::<Result<_, _>>()?,
+                        needed: number(value, "votes_needed")? as usize,
+                    }),
+                },
+                clue: match value.get("clue") {
+                    None => None,
+                    Some(Value::Object(obj)) => Some(clue(obj)?),
+                    Some(_) => return Err(MsgParseError::InvalidJsonStructure),
+                },
+                guesses_left: match value.get("guesses_left") {
+                    None => None,
+                    Some(_) => Some(number(value, "guesses_left")? as usize),
+                },
+            })),
+            "reveal" => Ok(Event::Reveal(card(value)?)),
+            "spy" => Ok(Event::Spy(objects(value, "cards")?.into_iter().map(card).collect::<Result<_, _>>()?)),
+            "roster" => Ok(Event::Roster(objects(value, "players")?.into_iter().map(player).collect::<Result<_, _>>()?)),
+            "chat" => Ok(Event::Chat {
+                messages: objects(value, "messages")?.into_iter().map(chat_line).collect::<Result<_, _>>()?,
+                history: flag(value, "history")?,
+            }),
+            "error" => Ok(Event::Error(text(value, "message")?)),
+            "reload" => Ok(Event::Reload {
+                ident: optional_text(value, "ident")?,
+            }),
+            "turn" => Ok(Event::Turn(color(value, "team")?)),
+            "win" => Ok(Event::Win(color(value, "team")?)),
+            "shutdown" => Ok(Event::Shutdown),
+            _ => Err(MsgParseError::TypeError),
+        }
+    }
+}
+
+fn text(obj: &Map<String, Value>, key: &str) -> Result<String, MsgParseError> {
+    match obj.get(key) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        _ => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn optional_text(obj: &Map<String, Value>, key: &str) -> Result<Option<String>, MsgParseError> {
+    match obj.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => text(obj, key).map(Some),
+    }
+}
+
+fn texts(obj: &Map<String, Value>, key: &str) -> Result<Vec<String>, MsgParseError> {
+    match obj.get(key) {
+        Some(Value::Array(values)) => values.iter()
+            .map(|value| value.as_str().map(String::from).ok_or(MsgParseError::InvalidJsonStructure))
+            .collect(),
+        _ => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn number(obj: &Map<String, Value>, key: &str) -> Result<u64, MsgParseError> {
+    obj.get(key).and_then(Value::as_u64).ok_or(MsgParseError::InvalidJsonStructure)
+}
+
+fn color(obj: &Map<String, Value>, key: &str) -> Result<Color, MsgParseError> {
+    match obj.get(key) {
+        Some(Value::String(color)) if color == "red" => Ok(Color::Red),
+        Some(Value::String(color)) if color == "blue" => Ok(Color::Blue),
+        _ => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn team(obj: &Map<String, Value>, key: &str) -> Result<Team, MsgParseError> {
+    match obj.get(key) {
+        Some(Value::String(team)) if team == "none" => Ok(Team::None),
+        Some(Value::String(team)) if team == "death" => Ok(Team::Death),
+        _ => color(obj, key).map(Team::Player),
+    }
+}
+
+/// The objects in the array at `key`.
+fn objects<'a>(obj: &'a Map<String, Value>, key: &str) -> Result<Vec<&'a Map<String, Value>>, MsgParseError> {
+    match obj.get(key) {
+        Some(Value::Array(values)) => values.iter()
+            .map(|value| value.as_object().ok_or(MsgParseError::InvalidJsonStructure))
+            .collect(),
+        _ => Err(MsgParseError::InvalidJsonStructure),
+    }
+}
+
+fn card(obj: &Map<String, Value>) -> Result<Card, MsgParseError> {
+    Ok(Card {
+        word: text(obj, "word")?,
+        team: team(obj, "team")?,
+    })
+}
+
+fn clue(obj: &Map<String, Value>) -> Result<Clue, MsgParseError> {
+    Ok(Clue {
+        word: text(obj, "word")?,
+        count: number(obj, "count")? as usize,
+    })
+}
+
+fn player(obj: &Map<String, Value>) -> Result<Player, MsgParseError> {
+    Ok(Player {
+        name: text(obj, "name")?,
+        team: color(obj, "team")?,
+        spymaster: flag(obj, "spymaster")?,
+        captain: flag(obj, "captain")?,
+    })
+}
+
+fn chat_line(obj: &Map<String, Value>) -> Result<ChatLine, MsgParseError> {
+    Ok(ChatLine {
+        name: text(obj, "name")?,
+        team: color(obj, "team")?,
+        spymaster: flag(obj, "spymaster")?,
+        channel: Channel::parse(&text(obj, "channel")?).ok_or(MsgParseError::InvalidJsonStructure)?,
+        text: text(obj, "text")?,
+        sent: number(obj, "sent")?,
+    })
+}
+
+fn proposal(obj: &Map<String, Value>) -> Result<(String, Vec<String>), MsgParseError> {
+    let voters = match obj.get("voters") {
+        Some(Value::Array(voters)) => voters.iter()
+            .map(|voter| voter.as_str().map(String::from).ok_or(MsgParseError::InvalidJsonStructure))
+            .collect::<Result<_, _>>()?,
+        _ => return Err(MsgParseError::InvalidJsonStructure),
+    };
+    Ok((text(obj, "word")?, voters))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use serde_json::{Map, Value};
+    use ws::Message;
+
+    use crate::game::chat::Channel;
+    use crate::game::clue::Clue;
+    use crate::game::{Color, Team};
+    use crate::protocol::codec::Encoding;
+    use crate::protocol::{Card, Chat, Event, Join, Msg, Player, Reveal, Role, ServerMsg, Step, Vote};
+
+    #[test]
+    fn msg_from_string() {
+        let expected = Msg {
+            game: "Abc".into(),
+            ident: "ABC123".to_string(),
+            role: Role::Player,
+            session: None,
+            seq: None,
+            steps: vec![
+                Step::Reveal(Reveal {
+                    word: "show".into(),
+                }),
+            ],
+        };
+        let actual = Msg::try_from(
+            &r#"{
+            "game": "Abc",
+            "ident": "ABC123",
+            "steps": [
+                {
+                    "type": "reveal",
+                    "word": "show"
+                }
+            ]
+            }"#.to_string()).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn spectator_msg_from_string() {
+        let msg = Msg::try_from(&r#"{"game": "Abc", "ident": "ABC123", "role": "spectator", "steps": []}"#.to_string()).unwrap();
+        assert_eq!(Role::Spectator, msg.role);
+        assert!(Msg::try_from(&r#"{"game": "Abc", "ident": "ABC123", "role": "admin", "steps": []}"#.to_string()).is_err());
+    }
+
+    #[test]
+    fn msg_from_message_pack() {
+        let value: Value = serde_json::from_str(r#"{"game": "Abc", "ident": "ABC123", "steps": [{"type": "skip"}]}"#).unwrap();
+        let msg = Msg::try_from(Encoding::MessagePack.encode(&value)).unwrap();
+        assert_eq!(vec![Step::Skip], msg.steps);
+        assert!(Msg::try_from(Message::Binary(vec![0xc1])).is_err());
+    }
+
+    #[test]
+    fn resuming_msg_from_string() {
+        let msg = Msg::try_from(&r#"{"game": "Abc", "ident": "ABC123", "session": "s1", "seq": 12, "steps": []}"#.to_string()).unwrap();
+        assert_eq!(Some("s1".to_string()), msg.session);
+        assert_eq!(Some(12), msg.seq);
+        assert!(Msg::try_from(&r#"{"game": "Abc", "ident": "ABC123", "seq": -1, "steps": []}"#.to_string()).is_err());
+    }
+
+    #[test]
+    fn reveal_from_map() {
+        let expected = Reveal {
+            word: "house".into(),
+        };
+        let mut map = Map::new();
+        map.insert("type".into(), Value::String("reveal".into()));
+        map.insert("word".into(), Value::String("house".into()));
+        let actual = Reveal::try_from(
+            &map
+        ).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn step_from_map() {
+        let expected = Step::Reveal(Reveal {
+            word: "house".into(),
+        });
+        let mut map = Map::new();
+        map.insert("type".into(), Value::String("reveal".into()));
+        map.insert("word".into(), Value::String("house".into()));
+        let actual = Step::try_from(
+            &map
+        ).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn join_from_map() {
+        let map: Map<String, Value> = serde_json::from_str(r#"{"type": "join", "name": "Ann", "team": "blue", "spymaster": true}"#).unwrap();
+        let expected = Step::Join(Join {
+            name: "Ann".into(),
+            team: Color::Blue,
+            spymaster: true,
+            captain: false,
+        });
+        assert_eq!(expected, Step::try_from(&map).unwrap());
+        let map: Map<String, Value> = serde_json::from_str(r#"{"type": "join", "name": "Ann", "team": "green"}"#).unwrap();
+        assert!(Step::try_from(&map).is_err());
+    }
+
+    #[test_case(r#"{"type": "chat", "text": "hi"}"# => Some(Step::Chat(Chat { channel: Channel::Global, text: "hi".into() })))]
+    #[test_case(r#"{"type": "chat", "channel": "team", "text": "hi"}"# => Some(Step::Chat(Chat { channel: Channel::Team, text: "hi".into() })))]
+    #[test_case(r#"{"type": "chat", "channel": "red", "text": "hi"}"# => None)]
+    #[test_case(r#"{"type": "chat"}"# => None)]
+    fn chat_from_map(json: &str) -> Option<Step> {
+        let map: Map<String, Value> = serde_json::from_str(json).unwrap();
+        Step::try_from(&map).ok()
+    }
+
+    #[test]
+    fn vote_from_map() {
+        let map: Map<String, Value> = serde_json::from_str(r#"{"type": "vote", "word": "boat"}"#).unwrap();
+        assert_eq!(Step::Vote(Vote { word: "boat".into() }), Step::try_from(&map).unwrap());
+    }
+
+    #[test_case("player" => Some(Role::Player))]
+    #[test_case("spectator" => Some(Role::Spectator))]
+    #[test_case("admin" => None)]
+    fn parse_role(value: &str) -> Option<Role> {
+        Role::parse(value)
+    }
+
+    #[test]
+    fn msg_round_trip() {
+        let msg = || Msg {
+            game: "Abc".into(),
+            ident: "ABC123".into(),
+            role: Role::Spectator,
+            session: Some("s1".into()),
+            seq: Some(4),
+            steps: vec![
+                Step::Join(Join { name: "Ann".into(), team: Color::Red, spymaster: false, captain: true }),
+                Step::Chat(Chat { channel: Channel::Team, text: "hi".into() }),
+                Step::Vote(Vote { word: "boat".into() }),
+                Step::Clue(Clue { word: "ocean".into(), count: 2 }),
+                Step::Skip,
+            ],
+        };
+        let value: Value = msg().into();
+        assert_eq!(msg(), Msg::try_from(value).unwrap());
+    }
+
+    #[test]
+    fn server_msg_from_value() {
+        let value: Value = serde_json::from_str(r#"{
+            "game": "Abc",
+            "seq": 7,
+            "steps": [
+                {"type": "reveal", "word": "boat", "team": "death"},
+                {"type": "roster", "players": [{"name": "Ann", "team": "blue", "spymaster": true, "captain": false}]},
+                {"type": "hologram"},
+                null
+            ]
+        }"#).unwrap();
+        let expected = ServerMsg {
+            game: Some("Abc".into()),
+            seq: Some(7),
+            events: vec![
+                Event::Reveal(Card { word: "boat".into(), team: Team::Death }),
+                Event::Roster(vec![Player { name: "Ann".into(), team: Color::Blue, spymaster: true, captain: false }]),
+            ],
+        };
+        assert_eq!(expected, ServerMsg::try_from(value).unwrap());
+    }
+
+    #[test]
+    fn server_msg_from_message_pack() {
+        let value: Value = serde_json::from_str(r#"{"steps": [{"type": "error", "message": "nope"}]}"#).unwrap();
+        let msg = ServerMsg::try_from(Encoding::MessagePack.encode(&value)).unwrap();
+        assert_eq!(vec![Event::Error("nope".into())], msg.events);
+        assert!(ServerMsg::try_from(Message::Text("{\"steps\": [{\"type\": \"win\"}]}".into())).is_err());
+    }
+}
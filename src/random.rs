@@ -1,30 +1,188 @@
+use std::fmt::Display;
+
 use rand::Rng;
-use rand::seq::SliceRandom;
+use serde::export::Formatter;
+
+#[derive(Debug, PartialEq)]
+pub enum SampleError {
+    NotEnoughElements { available: usize, requested: usize },
+    InvalidWeight(f64),
+}
+
+impl Display for SampleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleError::NotEnoughElements { available, requested } =>
+                write!(f, "cannot pick {} of {} elements", requested, available),
+            SampleError::InvalidWeight(weight) => write!(f, "weights must be finite and not negative, got {}", weight),
+        }
+    }
+}
 
 pub trait GetRandom<T> {
+    fn get_random<R: Rng>(self, rng: &mut R) -> Option<T>;
 
-    fn get_random(self) -> Option<T>;
+    /// `n` distinct elements in random order, every subset being equally likely.
+    fn get_n_random<R: Rng>(self, n: usize, rng: &mut R) -> Result<Vec<T>, SampleError>;
 
-    fn get_n_random(self, n: usize) -> Vec<T>;
+    /// `n` distinct elements, each draw picking from the remaining elements proportional to `weight`.
+    /// Elements weighing 0 are only picked once all others are taken.
+    fn get_n_weighted<R: Rng, F: Fn(&T) -> f64>(self, n: usize, weight: F, rng: &mut R) -> Result<Vec<T>, SampleError>;
+}
+
+impl<T> GetRandom<T> for &[T] where T: Clone {
+    fn get_random<R: Rng>(self, rng: &mut R) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self[rng.gen_range(0, self.len())].clone())
+        }
+    }
+
+    fn get_n_random<R: Rng>(self, n: usize, rng: &mut R) -> Result<Vec<T>, SampleError> {
+        check_size(self.len(), n)?;
+        // partial Fisher-Yates: only the first n positions get shuffled
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        for i in 0..n {
+            let j = rng.gen_range(i, indices.len());
+            indices.swap(i, j);
+        }
+        Ok(indices[..n].iter().map(|&index| self[index].clone()).collect())
+    }
+
+    fn get_n_weighted<R: Rng, F: Fn(&T) -> f64>(self, n: usize, weight: F, rng: &mut R) -> Result<Vec<T>, SampleError> {
+        check_size(self.len(), n)?;
+        // Efraimidis-Spirakis: the n largest keys u^(1/w) form a weighted sample, compared as ln(u)/w
+        let mut keyed = Vec::with_capacity(self.len());
+        for (index, element) in self.iter().enumerate() {
+            let w = weight(element);
+            if !w.is_finite() || w < 0.0 {
+                return Err(SampleError::InvalidWeight(w));
+            }
+            let u: f64 = 1.0 - rng.gen::<f64>();
+            let key = if w == 0.0 { std::f64::NEG_INFINITY } else { u.ln() / w };
+            keyed.push((key, rng.gen::<u64>(), index));
+        }
+        // the random tie breaker keeps the order of zero weight elements random
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)));
+        Ok(keyed[..n].iter().map(|&(_, _, index)| self[index].clone()).collect())
+    }
 }
 
 impl<T> GetRandom<T> for &Vec<T> where T: Clone {
-    fn get_random(self) -> Option<T> {
-        self.choose(&mut rand::thread_rng()).cloned()
+    fn get_random<R: Rng>(self, rng: &mut R) -> Option<T> {
+        self.as_slice().get_random(rng)
+    }
+
+    fn get_n_random<R: Rng>(self, n: usize, rng: &mut R) -> Result<Vec<T>, SampleError> {
+        self.as_slice().get_n_random(n, rng)
+    }
+
+    fn get_n_weighted<R: Rng, F: Fn(&T) -> f64>(self, n: usize, weight: F, rng: &mut R) -> Result<Vec<T>, SampleError> {
+        self.as_slice().get_n_weighted(n, weight, rng)
+    }
+}
+
+fn check_size(available: usize, requested: usize) -> Result<(), SampleError> {
+    if requested > available {
+        Err(SampleError::NotEnoughElements { available, requested })
+    } else {
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::random::{GetRandom, SampleError};
 
-    fn get_n_random(self, n: usize) -> Vec<T> where T: Clone {
-        if n > self.len() {
-            panic!("Trying to get more elements out of vec than it contains!")
+    const DRAWS: usize = 20_000;
+
+    proptest! {
+        #[test]
+        fn picks_n_distinct_elements(len in 0usize..60, n in 0usize..60, seed: u64) {
+            let elements: Vec<usize> = (0..len).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            match elements.get_n_random(n, &mut rng) {
+                Ok(picked) => {
+                    prop_assert_eq!(n, picked.len());
+                    prop_assert_eq!(n, picked.iter().collect::<HashSet<_>>().len());
+                }
+                Err(e) => {
+                    prop_assert!(n > len);
+                    prop_assert_eq!(SampleError::NotEnoughElements { available: len, requested: n }, e);
+                }
+            }
         }
-        let mut indices: Vec<usize> = (0..self.len()).collect();
-        let mut rng = rand::thread_rng();
-        let mut selected: Vec<T> = Vec::new();
-        for _ in 0..n {
-            let index = rng.gen_range(0, indices.len());
-            let index = indices.remove(index);
-            selected.push(self.get(index).unwrap().clone())
+
+        #[test]
+        fn weighted_picks_n_distinct_elements(weights in prop::collection::vec(0.0f64..10.0, 0..40), n in 0usize..40, seed: u64) {
+            let elements: Vec<usize> = (0..weights.len()).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+            match elements.get_n_weighted(n, |&i| weights[i], &mut rng) {
+                Ok(picked) => prop_assert_eq!(n, picked.iter().collect::<HashSet<_>>().len()),
+                Err(_) => prop_assert!(n > weights.len()),
+            }
+        }
+
+        #[test]
+        fn every_element_equally_likely(len in 2usize..8, seed: u64) {
+            let elements: Vec<usize> = (0..len).collect();
+            let n = len / 2;
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut counts = vec![0usize; len];
+            for _ in 0..DRAWS {
+                for i in elements.get_n_random(n, &mut rng).unwrap() {
+                    counts[i] += 1;
+                }
+            }
+            let expected = (DRAWS * n) as f64 / len as f64;
+            for count in counts {
+                prop_assert!((count as f64 - expected).abs() < expected * 0.1, "{} far from {}", count, expected);
+            }
         }
-        selected
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn first_pick_proportional_to_weight() {
+        let elements = vec![1.0, 2.0, 7.0];
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = vec![0usize; 3];
+        for _ in 0..DRAWS {
+            let picked = elements.get_n_weighted(1, |&w| w, &mut rng).unwrap();
+            counts[elements.iter().position(|&w| w == picked[0]).unwrap()] += 1;
+        }
+        for (count, weight) in counts.iter().zip(&elements) {
+            let expected = DRAWS as f64 * weight / 10.0;
+            assert!((*count as f64 - expected).abs() < expected * 0.1, "{} far from {}", count, expected);
+        }
+    }
+
+    #[test]
+    fn zero_weight_only_as_last_resort() {
+        let elements = vec![0.0, 1.0, 0.0, 3.0];
+        for seed in 0..20 {
+            let picked = elements.get_n_weighted(3, |&w| w, &mut StdRng::seed_from_u64(seed)).unwrap();
+            assert!(picked[..2].contains(&1.0) && picked[..2].contains(&3.0));
+            assert_eq!(0.0, picked[2]);
+        }
+    }
+
+    #[test_case(-1.0)]
+    #[test_case(std::f64::NAN)]
+    fn invalid_weights(weight: f64) {
+        let elements = vec![1.0, weight];
+        assert!(elements.get_n_weighted(1, |&w| w, &mut StdRng::seed_from_u64(1)).is_err());
+    }
+
+    #[test]
+    fn empty_has_no_random_element() {
+        let elements: Vec<u8> = vec![];
+        assert_eq!(None, elements.get_random(&mut StdRng::seed_from_u64(1)));
+    }
+}
@@ -1 +1,2 @@
-pub mod words;
\ No newline at end of file
+pub mod validate;
+pub mod words;
@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use serde::export::Formatter;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::similarity::levenshtein;
+
+/// Minimum number of words a language needs to fill a board.
+pub const MIN_WORDS: usize = 25;
+
+/// Shortest word that is checked for being part of another word; shorter ones match far too often.
+const MIN_CONTAINED_LENGTH: usize = 3;
+const MIN_STEM_LENGTH: usize = 5;
+const MAX_STEM_SUFFIX: usize = 3;
+
+/// NFC normalized word without surrounding whitespace, as stored on the board.
+pub fn normalize(word: &str) -> String {
+    word.trim().nfc().collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Issue {
+    TooFew(usize),
+    Empty,
+    Whitespace(String),
+    NotNormalized(String),
+    Duplicate(String),
+    CaseDuplicate(String, String),
+    Plural(String, String),
+    Contains(String, String),
+    SharedStem(String, String),
+    SimilarSpelling(String, String),
+}
+
+impl Issue {
+    /// Errors make a game unplayable or ambiguous, everything else is a warning.
+    pub fn is_error(&self) -> bool {
+        match self {
+            Issue::TooFew(_) | Issue::Empty | Issue::Duplicate(_) | Issue::CaseDuplicate(_, _) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::TooFew(n) => write!(f, "only {} words, at least {} are needed", n, MIN_WORDS),
+            Issue::Empty => write!(f, "contains an empty word"),
+            Issue::Whitespace(w) => write!(f, "{:?} has surrounding whitespace", w),
+            Issue::NotNormalized(w) => write!(f, "{:?} is not NFC normalized", w),
+            Issue::Duplicate(w) => write!(f, "{:?} appears more than once", w),
+            Issue::CaseDuplicate(a, b) => write!(f, "{:?} and {:?} only differ in case", a, b),
+            Issue::Plural(a, b) => write!(f, "{:?} is a plural of {:?}", b, a),
+            Issue::Contains(a, b) => write!(f, "{:?} contains {:?}", a, b),
+            Issue::SharedStem(a, b) => write!(f, "{:?} and {:?} share a stem", a, b),
+            Issue::SimilarSpelling(a, b) => write!(f, "{:?} and {:?} are spelled almost the same", a, b),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub words: usize,
+    pub unique: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    pub avg_length: f64,
+    pub multi_word: usize,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} words ({} unique), length {}-{} (avg {:.1}), {} with spaces",
+            self.words, self.unique, self.min_length, self.max_length, self.avg_length, self.multi_word
+        )
+    }
+}
+
+pub struct Report {
+    pub stats: Stats,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.is_error()).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.len() - self.errors()
+    }
+}
+
+pub fn validate(words: &[String]) -> Report {
+    let mut issues = Vec::new();
+    if words.len() < MIN_WORDS {
+        issues.push(Issue::TooFew(words.len()));
+    }
+    let mut seen = HashSet::new();
+    let mut unique = Vec::new();
+    for word in words {
+        if word.trim().is_empty() {
+            issues.push(Issue::Empty);
+            continue;
+        }
+        if word.trim() != word {
+            issues.push(Issue::Whitespace(word.clone()));
+        }
+        let normalized = normalize(word);
+        if normalized != word.trim() {
+            issues.push(Issue::NotNormalized(word.clone()));
+        }
+        if seen.insert(normalized.clone()) {
+            unique.push(normalized);
+        } else {
+            issues.push(Issue::Duplicate(normalized));
+        }
+    }
+    for (i, a) in unique.iter().enumerate() {
+        for b in unique.iter().skip(i + 1) {
+            if let Some(issue) = relation(a, b) {
+                issues.push(issue);
+            }
+        }
+    }
+    Report {
+        stats: stats(words.len(), &unique),
+        issues,
+    }
+}
+
+/// The most severe way two distinct words could be confused on the board, if any.
+fn relation(a: &str, b: &str) -> Option<Issue> {
+    let lower_a = a.to_lowercase();
+    let lower_b = b.to_lowercase();
+    let (short, long, lower_short, lower_long) = if lower_a.chars().count() <= lower_b.chars().count() {
+        (a, b, &lower_a, &lower_b)
+    } else {
+        (b, a, &lower_b, &lower_a)
+    };
+    if lower_short == lower_long {
+        return Some(Issue::CaseDuplicate(a.into(), b.into()));
+    }
+    if is_plural(lower_short, lower_long) {
+        return Some(Issue::Plural(short.into(), long.into()));
+    }
+    if lower_short.chars().count() >= MIN_CONTAINED_LENGTH && lower_long.contains(lower_short.as_str()) {
+        return Some(Issue::Contains(long.into(), short.into()));
+    }
+    let prefix = lower_short.chars().zip(lower_long.chars()).take_while(|(x, y)| x == y).count();
+    if prefix >= MIN_STEM_LENGTH && lower_long.chars().count() - prefix <= MAX_STEM_SUFFIX {
+        return Some(Issue::SharedStem(short.into(), long.into()));
+    }
+    if lower_short.chars().count() >= 4 && levenshtein(lower_short, lower_long) <= 1 {
+        return Some(Issue::SimilarSpelling(short.into(), long.into()));
+    }
+    None
+}
+
+fn is_plural(singular: &str, plural: &str) -> bool {
+    plural == format!("{}s", singular)
+        || plural == format!("{}es", singular)
+        || (singular.ends_with('y') && plural == format!("{}ies", &singular[..singular.len() - 1]))
+}
+
+fn stats(words: usize, unique: &[String]) -> Stats {
+    let lengths: Vec<usize> = unique.iter().map(|w| w.chars().count()).collect();
+    Stats {
+        words,
+        unique: unique.len(),
+        min_length: lengths.iter().cloned().min().unwrap_or(0),
+        max_length: lengths.iter().cloned().max().unwrap_or(0),
+        avg_length: if lengths.is_empty() { 0.0 } else { lengths.iter().sum::<usize>() as f64 / lengths.len() as f64 },
+        multi_word: unique.iter().filter(|w| w.contains(char::is_whitespace)).count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::res::validate::{normalize, relation, validate, Issue};
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_composes_and_trims() {
+        assert_eq!("B\u{e4}r", normalize(" Ba\u{308}r "));
+    }
+
+    #[test_case("ape", "Ape" => Some(Issue::CaseDuplicate("ape".into(), "Ape".into())))]
+    #[test_case("card", "cards" => Some(Issue::Plural("card".into(), "cards".into())))]
+    #[test_case("party", "parties" => Some(Issue::Plural("party".into(), "parties".into())))]
+    #[test_case("paper", "ape" => Some(Issue::Contains("paper".into(), "ape".into())))]
+    #[test_case("theater", "theatre" => Some(Issue::SharedStem("theater".into(), "theatre".into())))]
+    #[test_case("horse", "house" => Some(Issue::SimilarSpelling("horse".into(), "house".into())))]
+    #[test_case("oak", "piano" => None)]
+    fn relations(a: &str, b: &str) -> Option<Issue> {
+        relation(a, b)
+    }
+
+    #[test]
+    fn duplicates_after_normalization_are_errors() {
+        let report = validate(&words(&["B\u{e4}r", "Ba\u{308}r ", "Affe"]));
+        assert!(report.issues.contains(&Issue::Duplicate("B\u{e4}r".into())));
+        assert!(report.issues.contains(&Issue::Whitespace("Ba\u{308}r ".into())));
+        assert!(report.issues.contains(&Issue::NotNormalized("Ba\u{308}r ".into())));
+        assert!(report.issues.contains(&Issue::TooFew(3)));
+        assert_eq!(2, report.stats.unique);
+        assert_eq!(2, report.errors());
+    }
+
+    #[test_case("english")]
+    #[test_case("german")]
+    fn built_in_packs_have_no_errors(language: &str) {
+        let report = validate(&crate::res::words::words(language).unwrap());
+        let errors: Vec<String> = report.issues.iter()
+            .filter(|issue| issue.is_error())
+            .map(|issue| issue.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
@@ -1,28 +1,295 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::random::GetRandom;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::export::Formatter;
+
+use crate::random::{GetRandom, SampleError};
+use crate::res::validate::{normalize, validate, Issue, MIN_WORDS};
+
+/// Most words players may paste for a single game.
+pub const MAX_CUSTOM_WORDS: usize = 1000;
 
 lazy_static! {
-    static ref WORDS: HashMap<String, Vec<String>> = {
+    static ref WORDS: HashMap<String, Vec<Word>> = {
         let data = include_bytes!("words.json");
         let data = std::str::from_utf8(data).unwrap();
-        let map: HashMap<String, Vec<String>> = serde_json::from_str(data).unwrap();
+        let mut map = parse_pack(data).unwrap();
+        for dir in &crate::conf::settings().words.dirs {
+            match load_dir(Path::new(dir)) {
+                Ok(packs) => merge(&mut map, packs),
+                Err(e) => warn!("Skipping word pack directory {}: {:?}", dir, e),
+            }
+        }
         map.into_iter()
-            .filter(|(_, list)| list.len() >= 25)
+            .map(|(language, list)| {
+                let report = validate(&plain(&list));
+                for issue in &report.issues {
+                    if issue.is_error() {
+                        warn!("Word pack {}: {}", language, issue);
+                    } else {
+                        debug!("Word pack {}: {}", language, issue);
+                    }
+                }
+                (language, normalized(list))
+            })
+            .filter(|(_, list)| list.len() >= MIN_WORDS)
             .collect()
     };
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    fn distance(&self, other: &Difficulty) -> usize {
+        (*self as isize - *other as isize).abs() as usize
+    }
+
+    pub fn parse(value: &str) -> Option<Difficulty> {
+        match value {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Medium
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        })
+    }
+}
+
+/// A word in a pack. Packs may list plain strings, which get no tags and medium difficulty.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Word {
+    pub word: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+impl From<&str> for Word {
+    fn from(word: &str) -> Self {
+        Self {
+            word: word.to_string(),
+            tags: vec![],
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Plain(String),
+    Tagged(Word),
+}
+
+impl From<Entry> for Word {
+    fn from(entry: Entry) -> Self {
+        match entry {
+            Entry::Plain(word) => Word::from(word.as_str()),
+            Entry::Tagged(word) => word,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NoSuchLanguageError(String);
 
+/// Percentage of the board drawn from a language other than the main one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LanguageShare {
+    pub language: String,
+    pub percent: u8,
+}
+
+/// A word drawn for a board with the language it came from, `None` for custom words.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawnWord {
+    pub word: String,
+    pub language: Option<String>,
+}
+
+/// Which words may end up on a board.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WordSelection {
+    /// The main language, filling whatever the custom words and `other_languages` leave.
+    pub language: String,
+    /// Further languages for bilingual boards.
+    #[serde(default)]
+    pub other_languages: Vec<LanguageShare>,
+    /// Only words with at least one of these tags, all words if empty.
+    #[serde(default)]
+    pub include_tags: Vec<String>,
+    /// No words with any of these tags.
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+    /// Prefer words of this difficulty, falling back to the closest ones.
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+    /// Words supplied by the players for this game only, see `custom_words`.
+    #[serde(default)]
+    pub custom_words: Vec<String>,
+    /// Percentage of the board drawn from `custom_words`, the rest comes from `language`.
+    #[serde(default)]
+    pub custom_percent: u8,
+}
+
+impl WordSelection {
+    pub fn language(language: &str) -> Self {
+        Self {
+            language: language.to_string(),
+            ..WordSelection::default()
+        }
+    }
+
+    fn accepts(&self, word: &Word) -> bool {
+        let included = self.include_tags.is_empty() || word.tags.iter().any(|tag| self.include_tags.contains(tag));
+        let excluded = word.tags.iter().any(|tag| self.exclude_tags.contains(tag));
+        included && !excluded
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WordSelectionError {
+    NoSuchLanguage(String),
+    NotEnoughWords { available: usize, needed: usize },
+    NotEnoughCustomWords { available: usize, needed: usize },
+    SharesTooLarge(usize),
+    Sampling(SampleError),
+}
+
+impl Display for WordSelectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WordSelectionError::NoSuchLanguage(language) => write!(f, "Unknown language '{}'.", language),
+            WordSelectionError::NotEnoughWords { available, needed } =>
+                write!(f, "Only {} words match the selected tags, {} are needed.", available, needed),
+            WordSelectionError::NotEnoughCustomWords { available, needed } =>
+                write!(f, "Only {} custom words were given, {} are needed.", available, needed),
+            WordSelectionError::SharesTooLarge(percent) =>
+                write!(f, "Custom words and other languages take {}% of the board, at most 100% are possible.", percent),
+            WordSelectionError::Sampling(e) => write!(f, "Could not draw words: {}.", e),
+        }
+    }
+}
+
+impl From<NoSuchLanguageError> for WordSelectionError {
+    fn from(e: NoSuchLanguageError) -> Self {
+        WordSelectionError::NoSuchLanguage(e.0)
+    }
+}
+
+impl From<SampleError> for WordSelectionError {
+    fn from(e: SampleError) -> Self {
+        WordSelectionError::Sampling(e)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CustomWordsError {
+    TooMany(usize),
+    Invalid(Vec<String>),
+}
+
+impl Display for CustomWordsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CustomWordsError::TooMany(n) => write!(f, "{} custom words given, at most {} are allowed.", n, MAX_CUSTOM_WORDS),
+            CustomWordsError::Invalid(problems) => write!(f, "Invalid custom words: {}.", problems.join("; ")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WordPackError {
+    Io(PathBuf, std::io::Error),
+    Json(PathBuf, serde_json::Error),
+}
+
+fn parse_pack(data: &str) -> Result<HashMap<String, Vec<Word>>, serde_json::Error> {
+    let pack: HashMap<String, Vec<Entry>> = serde_json::from_str(data)?;
+    Ok(pack.into_iter()
+        .map(|(language, entries)| (language, entries.into_iter().map(Word::from).collect()))
+        .collect())
+}
+
+/// Reads every `*.json` file in `dir`. Each file maps languages to word lists, just like the built-in `words.json`.
+pub fn load_dir(dir: &Path) -> Result<HashMap<String, Vec<Word>>, WordPackError> {
+    let mut map = HashMap::new();
+    let entries = fs::read_dir(dir).map_err(|e| WordPackError::Io(dir.to_path_buf(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let data = fs::read_to_string(&path).map_err(|e| WordPackError::Io(path.clone(), e))?;
+        let pack = parse_pack(&data).map_err(|e| WordPackError::Json(path.clone(), e))?;
+        merge(&mut map, pack);
+    }
+    Ok(map)
+}
+
+pub fn plain(words: &[Word]) -> Vec<String> {
+    words.iter().map(|w| w.word.clone()).collect()
+}
+
+/// Normalizes all words and drops empty ones and exact duplicates, keeping the first occurrence.
+fn normalized(words: Vec<Word>) -> Vec<Word> {
+    let mut seen = HashSet::new();
+    words.into_iter()
+        .map(|word| Word {
+            word: normalize(&word.word),
+            ..word
+        })
+        .filter(|word| !word.word.is_empty())
+        .filter(|word| seen.insert(word.word.clone()))
+        .collect()
+}
+
+fn merge(map: &mut HashMap<String, Vec<Word>>, packs: HashMap<String, Vec<Word>>) {
+    for (language, words) in packs {
+        map.entry(language).or_insert_with(Vec::new).extend(words);
+    }
+}
+
 pub fn languages() -> Vec<String> {
-    let words: &HashMap<String, Vec<String>> = &WORDS;
+    let words: &HashMap<String, Vec<Word>> = &WORDS;
     words.keys().cloned().collect()
 }
 
 pub fn words(language: &str) -> Result<Vec<String>, NoSuchLanguageError> {
-    let words: &HashMap<String, Vec<String>> = &WORDS;
+    tagged_words(language).map(|found| plain(&found))
+}
+
+pub fn tagged_words(language: &str) -> Result<Vec<Word>, NoSuchLanguageError> {
+    let words: &HashMap<String, Vec<Word>> = &WORDS;
     if let Some(found) = words.get(language) {
         Ok(found.clone())
     } else {
@@ -30,22 +297,342 @@ pub fn words(language: &str) -> Result<Vec<String>, NoSuchLanguageError> {
     }
 }
 
-pub fn get_25_random(language: &str) -> Result<Vec<String>, NoSuchLanguageError> {
-    let words: &HashMap<String, Vec<String>> = &WORDS;
-    if let Some(found) = words.get(language) {
-        Ok(found.get_n_random(25))
-    } else {
-        Err(NoSuchLanguageError(language.to_string()))
+/// Number of words per tag in a language, sorted by tag.
+pub fn tags(language: &str) -> Result<BTreeMap<String, usize>, NoSuchLanguageError> {
+    let mut tags = BTreeMap::new();
+    for word in tagged_words(language)? {
+        for tag in word.tags {
+            *tags.entry(tag).or_insert(0) += 1;
+        }
+    }
+    Ok(tags)
+}
+
+/// Parses a word list pasted by players, one word per line or separated by commas.
+/// The list has to pass the same checks as a built-in pack, except for its size, which depends on the mix.
+pub fn custom_words(text: &str) -> Result<Vec<String>, CustomWordsError> {
+    let words: Vec<String> = text.split(|c| c == '\n' || c == ',')
+        .filter(|word| !word.trim().is_empty())
+        .map(|word| word.to_string())
+        .collect();
+    if words.len() > MAX_CUSTOM_WORDS {
+        return Err(CustomWordsError::TooMany(words.len()));
+    }
+    let problems: Vec<String> = validate(&words).issues.iter()
+        .filter(|issue| issue.is_error())
+        .filter(|issue| match issue {
+            Issue::TooFew(_) => false,
+            _ => true,
+        })
+        .map(|issue| issue.to_string())
+        .collect();
+    if !problems.is_empty() {
+        return Err(CustomWordsError::Invalid(problems));
+    }
+    Ok(words.iter().map(|word| normalize(word)).collect())
+}
+
+/// Splits `n` words by `percents`, which add up to 100. The words left after rounding down
+/// go to the largest remainders, earlier shares first, so the counts always add up to `n`.
+fn shares(n: usize, percents: &[usize]) -> Vec<usize> {
+    let mut counts: Vec<usize> = percents.iter().map(|percent| n * percent / 100).collect();
+    let mut order: Vec<usize> = (0..percents.len()).collect();
+    order.sort_by_key(|&i| Reverse(n * percents[i] % 100));
+    let missing = n - counts.iter().sum::<usize>();
+    for &i in order.iter().take(missing) {
+        counts[i] += 1;
+    }
+    counts
+}
+
+/// Draws `n` distinct words: the configured shares from the custom words and the other languages,
+/// the rest from the main language, honoring the tag filters and preferring the requested difficulty.
+/// Words from `recent` boards (newest first) are only used when there are not enough other words,
+/// and then the ones used longest ago are the most likely.
+pub fn select_words<R: Rng>(selection: &WordSelection, n: usize, recent: &[Vec<String>], rng: &mut R) -> Result<Vec<DrawnWord>, WordSelectionError> {
+    let custom_percent = if selection.custom_words.is_empty() { 0 } else { selection.custom_percent };
+    let total_percent = custom_percent as usize + selection.other_languages.iter().map(|other| other.percent as usize).sum::<usize>();
+    if total_percent > 100 {
+        return Err(WordSelectionError::SharesTooLarge(total_percent));
+    }
+
+    let mut percents = vec![custom_percent as usize];
+    percents.extend(selection.other_languages.iter().map(|other| other.percent as usize));
+    percents.push(100 - total_percent);
+    let counts = shares(n, &percents);
+
+    let custom_needed = counts[0];
+    let custom: Vec<Word> = selection.custom_words.iter().map(|word| Word::from(word.as_str())).collect();
+    if custom.len() < custom_needed {
+        return Err(WordSelectionError::NotEnoughCustomWords { available: custom.len(), needed: custom_needed });
+    }
+    let mut selected: Vec<DrawnWord> = pick(custom, custom_needed, None, recent, rng)?
+        .into_iter()
+        .map(|word| DrawnWord { word, language: None })
+        .collect();
+
+    // the main language must exist even when the other shares leave no words for it
+    tagged_words(&selection.language)?;
+    let languages = selection.other_languages.iter()
+        .map(|other| other.language.as_str())
+        .chain(std::iter::once(selection.language.as_str()));
+    for (language, &needed) in languages.zip(&counts[1..]) {
+        if needed == 0 {
+            continue;
+        }
+        let candidates: Vec<Word> = tagged_words(language)?
+            .into_iter()
+            .filter(|word| selection.accepts(word))
+            .filter(|word| !selected.iter().any(|drawn| drawn.word == word.word))
+            .collect();
+        if candidates.len() < needed {
+            return Err(WordSelectionError::NotEnoughWords { available: candidates.len(), needed });
+        }
+        selected.extend(pick(candidates, needed, selection.difficulty, recent, rng)?
+            .into_iter()
+            .map(|word| DrawnWord { word, language: Some(language.to_string()) }));
+    }
+    selected.shuffle(rng);
+    Ok(selected)
+}
+
+/// Takes whole groups of equally preferable candidates, best first, and samples the group that does not fit entirely.
+/// Fresh words come before recent ones, then the closest difficulty; within a group older words weigh more.
+fn pick<R: Rng>(candidates: Vec<Word>, n: usize, difficulty: Option<Difficulty>, recent: &[Vec<String>], rng: &mut R) -> Result<Vec<String>, SampleError> {
+    let mut candidates: Vec<(bool, usize, usize, String)> = candidates.into_iter()
+        .map(|word| {
+            let distance = difficulty.map(|target| word.difficulty.distance(&target)).unwrap_or(0);
+            let penalty = recency_penalty(&word.word, recent);
+            (penalty > 0, distance, penalty, word.word)
+        })
+        .collect();
+    candidates.sort();
+    let n = n.min(candidates.len());
+    if n == 0 {
+        return Ok(vec![]);
     }
+    let boundary = (candidates[n - 1].0, candidates[n - 1].1);
+    let mut picked: Vec<String> = candidates.iter()
+        .take_while(|(used, distance, _, _)| (*used, *distance) < boundary)
+        .map(|(_, _, _, word)| word.clone())
+        .collect();
+    let group: Vec<(usize, String)> = candidates.into_iter()
+        .filter(|(used, distance, _, _)| (*used, *distance) == boundary)
+        .map(|(_, _, penalty, word)| (penalty, word))
+        .collect();
+    let missing = n - picked.len();
+    let sample = group.get_n_weighted(missing, |(penalty, _)| 1.0 / (*penalty).max(1) as f64, rng)?;
+    picked.extend(sample.into_iter().map(|(_, word)| word));
+    Ok(picked)
+}
+
+/// 0 for words not on any recent board, higher the more recently a word was used.
+fn recency_penalty(word: &str, recent: &[Vec<String>]) -> usize {
+    recent.iter()
+        .position(|board| board.iter().any(|w| w == word))
+        .map(|age| recent.len() - age)
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::get_25_random;
+    use std::fs;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{custom_words, DrawnWord, LanguageShare, load_dir, normalized, parse_pack, pick, plain, recency_penalty, select_words, shares, tagged_words, CustomWordsError, Difficulty, Word, WordSelection, WordSelectionError};
 
     #[test_case("german" => 25)]
     #[test_case("english" => 25)]
-    fn get_25_gets_25(language: &str) -> usize {
-        get_25_random(language).unwrap().len()
+    fn select_gets_25(language: &str) -> usize {
+        select_words(&WordSelection::language(language), 25, &[], &mut rand::thread_rng()).unwrap().len()
+    }
+
+    #[test]
+    fn load_dir_merges_packs() {
+        let dir = std::env::temp_dir().join(format!("codenamer-words-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.json"), r#"{"english": ["ape", "book"]}"#).unwrap();
+        fs::write(dir.join("b.json"), r#"{"english": ["car"], "german": ["Auto"]}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let packs = load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(3, packs["english"].len());
+        assert_eq!(1, packs["german"].len());
+    }
+
+    #[test]
+    fn parse_plain_and_tagged_words() {
+        let pack = parse_pack(r#"{"english": ["ape", {"word": "oak", "tags": ["nature"], "difficulty": "hard"}]}"#).unwrap();
+        assert_eq!(Word::from("ape"), pack["english"][0]);
+        assert_eq!(Word {
+            word: "oak".into(),
+            tags: vec!["nature".into()],
+            difficulty: Difficulty::Hard,
+        }, pack["english"][1]);
+    }
+
+    #[test]
+    fn normalized_removes_duplicates_and_whitespace() {
+        let words = vec![Word::from(" ape"), Word::from("ape"), Word::from(""), Word::from("Ba\u{308}r")];
+        let words: Vec<String> = normalized(words).into_iter().map(|w| w.word).collect();
+        assert_eq!(vec!["ape".to_string(), "B\u{e4}r".into()], words);
+    }
+
+    #[test]
+    fn select_excludes_tags() {
+        let selection = WordSelection {
+            exclude_tags: vec!["animals".into()],
+            ..WordSelection::language("english")
+        };
+        let animals: Vec<String> = tagged_words("english").unwrap().into_iter()
+            .filter(|w| w.tags.contains(&"animals".to_string()))
+            .map(|w| w.word)
+            .collect();
+        let selected = select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)).unwrap();
+        assert!(selected.iter().all(|w| !animals.contains(&w.word)));
+    }
+
+    #[test]
+    fn select_prefers_difficulty() {
+        let selection = WordSelection {
+            difficulty: Some(Difficulty::Easy),
+            ..WordSelection::language("english")
+        };
+        let easy = tagged_words("english").unwrap().into_iter()
+            .filter(|w| w.difficulty == Difficulty::Easy)
+            .count();
+        let selected = select_words(&selection, easy, &[], &mut StdRng::seed_from_u64(1)).unwrap();
+        let words = tagged_words("english").unwrap();
+        assert!(selected.iter().all(|s| words.iter().any(|w| w.word == s.word && w.difficulty == Difficulty::Easy)));
+    }
+
+    #[test]
+    fn select_fails_without_enough_words() {
+        let selection = WordSelection {
+            include_tags: vec!["animals".into()],
+            ..WordSelection::language("english")
+        };
+        match select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)) {
+            Err(WordSelectionError::NotEnoughWords { needed: 25, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn select_avoids_recent_words() {
+        let all = plain(&tagged_words("english").unwrap());
+        let fresh = all.len() - 25;
+        let recent = vec![all[..fresh].to_vec()];
+        let selected = select_words(&WordSelection::language("english"), 25, &recent, &mut StdRng::seed_from_u64(3)).unwrap();
+        assert!(selected.iter().all(|w| !recent[0].contains(&w.word)));
+    }
+
+    #[test]
+    fn pick_prefers_words_used_longest_ago() {
+        let recent = vec![vec!["ape".to_string()], vec!["oak".to_string()]];
+        let candidates = vec![Word::from("ape"), Word::from("oak"), Word::from("car")];
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut oak = 0;
+        for _ in 0..1000 {
+            let picked = pick(candidates.clone(), 2, None, &recent, &mut rng).unwrap();
+            assert_eq!("car", picked[0]);
+            if picked[1] == "oak" {
+                oak += 1;
+            }
+        }
+        assert!(oak > 600, "oak picked {} times", oak);
+    }
+
+    #[test]
+    fn recency_penalty_prefers_older_boards() {
+        let recent = vec![vec!["ape".to_string()], vec!["oak".to_string()]];
+        assert_eq!(2, recency_penalty("ape", &recent));
+        assert_eq!(1, recency_penalty("oak", &recent));
+        assert_eq!(0, recency_penalty("car", &recent));
+    }
+
+    #[test]
+    fn custom_words_are_split_and_normalized() {
+        assert_eq!(vec!["ape".to_string(), "big cat".into(), "B\u{e4}r".into()], custom_words("ape,\n big cat \n\nBa\u{308}r").unwrap());
+    }
+
+    #[test]
+    fn custom_words_reject_duplicates() {
+        match custom_words("ape\nApe") {
+            Err(CustomWordsError::Invalid(problems)) => assert_eq!(1, problems.len()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn select_mixes_custom_words() {
+        let custom: Vec<String> = (0..20).map(|i| format!("custom{}", i)).collect();
+        let selection = WordSelection {
+            custom_words: custom.clone(),
+            custom_percent: 40,
+            ..WordSelection::language("english")
+        };
+        let selected = select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_eq!(10, selected.iter().filter(|w| custom.contains(&w.word) && w.language.is_none()).count());
+        assert_eq!(25, selected.len());
+    }
+
+    #[test]
+    fn select_fails_without_enough_custom_words() {
+        let custom: Vec<String> = (0..24).map(|i| format!("custom{}", i)).collect();
+        let selection = WordSelection {
+            custom_words: custom,
+            custom_percent: 100,
+            ..WordSelection::language("klingon")
+        };
+        match select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)) {
+            Err(WordSelectionError::NotEnoughCustomWords { available, needed }) => assert_eq!((24, 25), (available, needed)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn select_mixes_languages() {
+        let selection = WordSelection {
+            other_languages: vec![LanguageShare { language: "german".into(), percent: 40 }],
+            ..WordSelection::language("english")
+        };
+        let selected = select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)).unwrap();
+        let from = |language: &str| selected.iter().filter(|w| w.language == Some(language.to_string())).count();
+        assert_eq!((15, 10), (from("english"), from("german")));
+        let german = tagged_words("german").unwrap();
+        assert!(selected.iter()
+            .filter(|w| w.language == Some("german".to_string()))
+            .all(|w: &DrawnWord| german.iter().any(|g| g.word == w.word)));
+    }
+
+    #[test]
+    fn select_rejects_shares_over_100_percent() {
+        let selection = WordSelection {
+            other_languages: vec![LanguageShare { language: "german".into(), percent: 60 }],
+            custom_words: vec!["ape".into()],
+            custom_percent: 50,
+            ..WordSelection::language("english")
+        };
+        assert_eq!(Err(WordSelectionError::SharesTooLarge(110)), select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)));
+    }
+
+    #[test_case(25, vec![40, 0, 60] => vec![10, 0, 15] ; "exact")]
+    #[test_case(25, vec![50, 50, 0] => vec![13, 12, 0] ; "halves")]
+    #[test_case(25, vec![30, 30, 30, 10] => vec![8, 8, 7, 2] ; "thirds")]
+    #[test_case(0, vec![50, 50] => vec![0, 0] ; "empty board")]
+    fn shares_add_up(n: usize, percents: Vec<usize>) -> Vec<usize> {
+        shares(n, &percents)
+    }
+
+    #[test]
+    fn select_checks_main_language_without_share() {
+        let selection = WordSelection {
+            other_languages: vec![LanguageShare { language: "german".into(), percent: 100 }],
+            ..WordSelection::language("klingon")
+        };
+        assert_eq!(Err(WordSelectionError::NoSuchLanguage("klingon".into())), select_words(&selection, 25, &[], &mut StdRng::seed_from_u64(1)));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use ws::{CloseCode, Sender};
+
+use crate::lock_game_cache;
+use crate::metrics::METRICS;
+
+/// How long clients get to answer the close frame before the event loop stops.
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref SOCKET: Mutex<Option<Sender>> = Mutex::new(None);
+}
+
+static IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+static SOCKET_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// True once a shutdown signal was received. New games and connections are refused from then on.
+pub fn in_progress() -> bool {
+    IN_PROGRESS.load(Ordering::SeqCst)
+}
+
+/// Registers the broadcaster of the running socket server so it can be told about the shutdown.
+pub fn register_socket(broadcaster: Sender) {
+    *SOCKET.lock().unwrap() = Some(broadcaster);
+}
+
+/// Called by the socket server once its event loop has ended.
+pub fn socket_stopped() {
+    SOCKET_STOPPED.store(true, Ordering::SeqCst);
+}
+
+/// Handles SIGINT and SIGTERM by shutting down gracefully, forcing the exit once `deadline` has passed.
+pub fn install(deadline: Duration) {
+    let installed = ctrlc::set_handler(move || {
+        if IN_PROGRESS.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        info!("Shutting down, waiting at most {}s", deadline.as_secs());
+        spawn(move || {
+            sleep(deadline);
+            error!("Shutdown deadline exceeded, exiting");
+            std::process::exit(1);
+        });
+        std::process::exit(shutdown());
+    });
+    if let Err(e) = installed {
+        error!("Could not install signal handler: {}", e);
+    }
+}
+
+fn shutdown() -> i32 {
+    let mut code = 0;
+    if let Some(socket) = SOCKET.lock().unwrap().take() {
+        crate::web::socket::announce_shutdown();
+        // the event loop drops unsent frames when it stops, so the connections are closed first
+        if let Err(e) = socket.close(CloseCode::Away) {
+            warn!("Could not close client connections: {}", e);
+        }
+        let closing = Instant::now();
+        while METRICS.socket_connections.get() > 0 && closing.elapsed() < CLOSE_GRACE {
+            sleep(Duration::from_millis(50));
+        }
+        if let Err(e) = socket.shutdown() {
+            error!("Could not stop socket server: {}", e);
+            code = 1;
+        } else {
+            while !SOCKET_STOPPED.load(Ordering::SeqCst) {
+                sleep(Duration::from_millis(50));
+            }
+        }
+    }
+    let mut cache = lock_game_cache();
+    if let Err(e) = cache.flush() {
+        error!("Could not flush game cache: {:?}", e);
+        code = 1;
+    }
+    info!("Shutdown complete");
+    log::logger().flush();
+    code
+}
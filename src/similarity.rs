@@ -0,0 +1,70 @@
+use std::cmp::min;
+
+/// Number of single character insertions, deletions and substitutions needed to turn `a` into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = min(substitution, min(previous[j + 1], current[j]) + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns up to `limit` candidates that look like a misspelling of `needle`, closest first.
+pub fn similar<'a, I>(needle: &str, candidates: I, limit: usize) -> Vec<String> where I: IntoIterator<Item=&'a String> {
+    let needle = needle.to_lowercase();
+    let max_distance = std::cmp::max(2, needle.chars().count() / 3);
+    let mut found: Vec<(usize, String)> = candidates.into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = if lower.contains(&needle) || needle.contains(&lower) {
+                0
+            } else {
+                levenshtein(&needle, &lower)
+            };
+            if distance <= max_distance {
+                Some((distance, candidate.clone()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    found.sort();
+    found.into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::similarity::{levenshtein, similar};
+
+    #[test_case("", "" => 0)]
+    #[test_case("abc", "" => 3)]
+    #[test_case("kitten", "sitting" => 3)]
+    #[test_case("house", "mouse" => 1)]
+    #[test_case("fish", "fish" => 0)]
+    fn levenshtein_distance(a: &str, b: &str) -> usize {
+        levenshtein(a, b)
+    }
+
+    #[test]
+    fn similar_names_closest_first() {
+        let names: Vec<String> = vec!["lunch".into(), "launch".into(), "office".into()];
+        assert_eq!(vec!["lunch".to_string(), "launch".to_string()], similar("lnch", &names, 3));
+    }
+
+    #[test]
+    fn similar_respects_limit() {
+        let names: Vec<String> = vec!["abc1".into(), "abc2".into(), "abc3".into()];
+        assert_eq!(2, similar("abc", &names, 2).len());
+    }
+}
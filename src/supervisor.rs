@@ -0,0 +1,73 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Run the task again when it ends or panics, at most `max_restarts` times within `window`.
+    Restart { max_restarts: usize, window: Duration },
+    /// Exit the process when the task ends or panics.
+    Exit,
+}
+
+/// Runs `task` on its own thread and applies `policy` whenever it ends, which none of the server tasks should do.
+pub fn supervise(name: &'static str, policy: Policy, task: fn()) -> JoinHandle<()> {
+    spawn(move || {
+        let mut restarts: Vec<Instant> = Vec::new();
+        loop {
+            let outcome = catch_unwind(AssertUnwindSafe(task));
+            if crate::shutdown::in_progress() {
+                return;
+            }
+            match outcome {
+                Ok(()) => error!("{} stopped unexpectedly", name),
+                Err(_) => error!("{} panicked", name),
+            }
+            if !should_restart(policy, &mut restarts, Instant::now()) {
+                error!("{} cannot be recovered, exiting", name);
+                log::logger().flush();
+                std::process::exit(1);
+            }
+            warn!("Restarting {} ({} restarts)", name, restarts.len());
+            sleep(Duration::from_secs(1));
+        }
+    })
+}
+
+fn should_restart(policy: Policy, restarts: &mut Vec<Instant>, now: Instant) -> bool {
+    match policy {
+        Policy::Exit => false,
+        Policy::Restart { max_restarts, window } => {
+            restarts.retain(|restart| now.duration_since(*restart) < window);
+            if restarts.len() >= max_restarts {
+                false
+            } else {
+                restarts.push(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::supervisor::{should_restart, Policy};
+
+    #[test]
+    fn exit_never_restarts() {
+        assert!(!should_restart(Policy::Exit, &mut vec![], Instant::now()));
+    }
+
+    #[test]
+    fn restart_limited_within_window() {
+        let policy = Policy::Restart { max_restarts: 2, window: Duration::from_secs(60) };
+        let mut restarts = vec![];
+        let now = Instant::now();
+        assert!(should_restart(policy, &mut restarts, now));
+        assert!(should_restart(policy, &mut restarts, now));
+        assert!(!should_restart(policy, &mut restarts, now));
+        assert!(should_restart(policy, &mut restarts, now + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{stdout, Write};
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{poll, read, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Color as TermColor, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use serde::export::Formatter;
+
+use crate::client::{Client, ClientError};
+use crate::game::clue::Clue;
+use crate::game::{Color, Team};
+use crate::protocol::{ChatLine, Event, Player, Votes};
+
+const COLUMNS: usize = 5;
+const CARD_WIDTH: usize = 14;
+const CHAT_LINES: usize = 5;
+/// How long to wait for key presses before looking for new messages.
+const TICK: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum TuiError {
+    Client(ClientError),
+    Terminal(crossterm::ErrorKind),
+}
+
+impl Display for TuiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TuiError::Client(e) => write!(f, "{}", e),
+            TuiError::Terminal(e) => write!(f, "terminal error: {}", e),
+        }
+    }
+}
+
+impl From<ClientError> for TuiError {
+    fn from(e: ClientError) -> TuiError {
+        TuiError::Client(e)
+    }
+}
+
+impl From<crossterm::ErrorKind> for TuiError {
+    fn from(e: crossterm::ErrorKind) -> TuiError {
+        TuiError::Terminal(e)
+    }
+}
+
+/// Who is playing at this terminal.
+pub struct Seat {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub captain: bool,
+}
+
+/// Everything the terminal shows, built from the server's events.
+#[derive(Debug)]
+struct Board {
+    words: Vec<String>,
+    revealed: HashMap<String, Team>,
+    /// The key card, only known to spymasters.
+    key: HashMap<String, Team>,
+    turn: Color,
+    winner: Option<Color>,
+    votes: Option<Votes>,
+    clue: Option<Clue>,
+    guesses_left: Option<usize>,
+    players: Vec<Player>,
+    chat: Vec<ChatLine>,
+    status: Option<String>,
+    cursor: usize,
+}
+
+impl Board {
+    fn new() -> Self {
+        Self {
+            words: vec![],
+            revealed: HashMap::new(),
+            key: HashMap::new(),
+            turn: Color::Red,
+            winner: None,
+            votes: None,
+            clue: None,
+            guesses_left: None,
+            players: vec![],
+            chat: vec![],
+            status: None,
+            cursor: 0,
+        }
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::State(state) => {
+                self.words = state.words;
+                self.revealed = state.revealed.into_iter().map(|card| (card.word, card.team)).collect();
+                self.turn = state.team;
+                self.winner = state.winner;
+                self.votes = state.votes;
+                self.clue = state.clue;
+                self.guesses_left = state.guesses_left;
+                self.cursor = self.cursor.min(self.words.len().saturating_sub(1));
+            }
+            Event::Reveal(card) => {
+                self.revealed.insert(card.word, card.team);
+            }
+            Event::Spy(cards) => self.key = cards.into_iter().map(|card| (card.word, card.team)).collect(),
+            Event::Roster(players) => self.players = players,
+            Event::Chat { messages, history } => {
+                if history {
+                    self.chat.clear();
+                }
+                self.chat.extend(messages);
+                let skip = self.chat.len().saturating_sub(CHAT_LINES);
+                self.chat.drain(..skip);
+            }
+            Event::Error(message) => self.status = Some(message),
+            Event::Reload { .. } => {
+                self.revealed.clear();
+                self.key.clear();
+                self.winner = None;
+                self.status = Some("A new game started.".into());
+            }
+            Event::Turn(team) => self.turn = team,
+            Event::Win(team) => self.winner = Some(team),
+            Event::Shutdown => self.status = Some("The server is shutting down.".into()),
+            Event::Session { .. } => {}
+        }
+    }
+
+    fn move_cursor(&mut self, dx: isize, dy: isize) {
+        if self.words.is_empty() {
+            return;
+        }
+        let rows = (self.words.len() + COLUMNS - 1) / COLUMNS;
+        let x = (self.cursor % COLUMNS) as isize + dx;
+        let y = (self.cursor / COLUMNS) as isize + dy;
+        let x = x.max(0).min(COLUMNS as isize - 1) as usize;
+        let y = y.max(0).min(rows as isize - 1) as usize;
+        self.cursor = (x + y * COLUMNS).min(self.words.len() - 1);
+    }
+
+    /// The word under the cursor if it can still be picked.
+    fn selected(&self) -> Option<&str> {
+        self.words.get(self.cursor)
+            .filter(|word| !self.revealed.contains_key(*word))
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Move(isize, isize),
+    Pick,
+    Skip,
+    Quit,
+    Nothing,
+}
+
+fn action(key: KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
+        KeyCode::Left | KeyCode::Char('h') => Action::Move(-1, 0),
+        KeyCode::Right | KeyCode::Char('l') => Action::Move(1, 0),
+        KeyCode::Up | KeyCode::Char('k') => Action::Move(0, -1),
+        KeyCode::Down | KeyCode::Char('j') => Action::Move(0, 1),
+        KeyCode::Enter | KeyCode::Char(' ') => Action::Pick,
+        KeyCode::Char('s') => Action::Skip,
+        KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+        _ => Action::Nothing,
+    }
+}
+
+fn team_color(team: &Team) -> TermColor {
+    match team {
+        Team::Player(Color::Red) => TermColor::Red,
+        Team::Player(Color::Blue) => TermColor::Blue,
+        Team::None => TermColor::Grey,
+        Team::Death => TermColor::Magenta,
+    }
+}
+
+fn color(color: &Color) -> TermColor {
+    team_color(&Team::Player(color.clone()))
+}
+
+fn draw<W: Write>(out: &mut W, board: &Board, game: &str, seat: Option<&Seat>) -> crossterm::Result<()> {
+    queue!(out, Clear(ClearType::All), MoveTo(0, 0), Print(format!("Game: {}", game)))?;
+    let (label, team) = match &board.winner {
+        Some(winner) => ("Winner: ", winner),
+        None => ("Turn: ", &board.turn),
+    };
+    queue!(out, MoveTo(0, 1), Print(label), SetForegroundColor(color(team)), Print(team), ResetColor)?;
+    if let Some(seat) = seat {
+        let role = if seat.spymaster { "spymaster" } else { "operative" };
+        queue!(out, Print(format!("   You: {} ({} ", seat.name, role)), SetForegroundColor(color(&seat.team)),
+            Print(&seat.team), ResetColor, Print(")"))?;
+    }
+    if let Some(clue) = &board.clue {
+        let left = board.guesses_left.map(|left| format!(", {} guesses left", left)).unwrap_or_default();
+        queue!(out, MoveTo(0, 2), Print(format!("Clue: {}{}", clue, left)))?;
+    }
+    for (i, word) in board.words.iter().enumerate() {
+        let x = (i % COLUMNS * CARD_WIDTH) as u16;
+        let y = (3 + i / COLUMNS * 2) as u16;
+        let text = if i == board.cursor {
+            format!("[{:^width$}]", word, width = CARD_WIDTH - 3)
+        } else {
+            format!(" {:^width$} ", word, width = CARD_WIDTH - 3)
+        };
+        queue!(out, MoveTo(x, y))?;
+        if let Some(team) = board.revealed.get(word) {
+            queue!(out, SetBackgroundColor(team_color(team)), SetForegroundColor(TermColor::Black))?;
+        } else if let Some(team) = board.key.get(word) {
+            queue!(out, SetForegroundColor(team_color(team)), SetAttribute(Attribute::Bold))?;
+        }
+        queue!(out, Print(text), ResetColor, SetAttribute(Attribute::Reset))?;
+    }
+    let mut y = (3 + (board.words.len() + COLUMNS - 1) / COLUMNS * 2) as u16;
+    if let Some(votes) = &board.votes {
+        for (word, voters) in &votes.proposals {
+            queue!(out, MoveTo(0, y), Print(format!("{}: {} ({}/{})", word, voters.join(", "), voters.len(), votes.needed)))?;
+            y += 1;
+        }
+    }
+    for player in &board.players {
+        let role = if player.spymaster { " (spymaster)" } else if player.captain { " (captain)" } else { "" };
+        queue!(out, MoveTo(0, y), SetForegroundColor(color(&player.team)), Print(&player.name), ResetColor, Print(role))?;
+        y += 1;
+    }
+    y += 1;
+    for line in &board.chat {
+        queue!(out, MoveTo(0, y), SetForegroundColor(color(&line.team)), Print(&line.name), ResetColor,
+            Print(format!(": {}", line.text)))?;
+        y += 1;
+    }
+    if let Some(status) = &board.status {
+        queue!(out, MoveTo(0, y + 1), Print(status))?;
+    }
+    queue!(out, MoveTo(0, y + 2), Print("arrows/hjkl move, enter pick, s skip turn, q quit"))?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Puts the terminal back the way it was, also when the client fails.
+struct Screen;
+
+impl Screen {
+    fn enter() -> crossterm::Result<Screen> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Plays a game at the terminal until the player quits, joining as `seat` or watching without one.
+pub fn run(mut client: Client, seat: Option<Seat>, timeout: Duration) -> Result<(), TuiError> {
+    if let Some(seat) = &seat {
+        client.join(&seat.name, seat.team.clone(), seat.spymaster, seat.captain)?;
+        if seat.spymaster {
+            client.spy()?;
+        }
+    }
+    let spymaster = seat.as_ref().map(|seat| seat.spymaster).unwrap_or(false);
+    let _screen = Screen::enter()?;
+    let mut out = stdout();
+    let mut board = Board::new();
+    let mut dirty = true;
+    loop {
+        loop {
+            match client.try_recv() {
+                Ok(Some(msg)) => {
+                    for event in msg.events {
+                        if let Event::Reload { .. } = event {
+                            client.refresh()?;
+                            if spymaster {
+                                client.spy()?;
+                            }
+                        }
+                        board.apply(event);
+                    }
+                    dirty = true;
+                }
+                Ok(None) => break,
+                Err(ClientError::Closed) => {
+                    board.status = Some("Connection lost, reconnecting...".into());
+                    draw(&mut out, &board, client.game(), seat.as_ref())?;
+                    client.reconnect(timeout)?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if dirty {
+            draw(&mut out, &board, client.game(), seat.as_ref())?;
+            dirty = false;
+        }
+        if !poll(TICK)? {
+            continue;
+        }
+        if let TermEvent::Key(key) = read()? {
+            board.status = None;
+            match action(key) {
+                Action::Move(dx, dy) => board.move_cursor(dx, dy),
+                Action::Pick if spymaster => board.status = Some("Spymasters give clues, they do not pick cards.".into()),
+                Action::Pick => match (board.selected().map(String::from), board.votes.is_some()) {
+                    (Some(word), true) => client.vote(&word)?,
+                    (Some(word), false) => client.reveal(&word)?,
+                    (None, _) => {}
+                },
+                Action::Skip => client.skip()?,
+                Action::Quit => break,
+                Action::Nothing => {}
+            }
+        }
+        dirty = true;
+    }
+    client.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use crate::game::{Color, Team};
+    use crate::protocol::{Card, Event, State};
+    use crate::tui::{action, Action, Board};
+
+    fn board() -> Board {
+        let mut board = Board::new();
+        board.apply(Event::State(State {
+            words: (0..25).map(|i| format!("word{}", i)).collect(),
+            team: Color::Blue,
+            winner: None,
+            revealed: vec![Card { word: "word1".into(), team: Team::None }],
+            spectators: 0,
+            votes: None,
+            clue: None,
+            guesses_left: None,
+        }));
+        board
+    }
+
+    #[test]
+    fn events_update_board() {
+        let mut board = board();
+        assert_eq!(Color::Blue, board.turn);
+        board.apply(Event::Reveal(Card { word: "word2".into(), team: Team::Death }));
+        board.apply(Event::Win(Color::Red));
+        assert_eq!(Some(&Team::Death), board.revealed.get("word2"));
+        assert_eq!(Some(Color::Red), board.winner);
+        board.apply(Event::Reload { ident: Some("abc".into()) });
+        assert!(board.revealed.is_empty());
+        assert_eq!(None, board.winner);
+    }
+
+    #[test_case(&[(1, 0)], 1, None ; "revealed card")]
+    #[test_case(&[(1, 0), (1, 0)], 2, Some("word2") ; "unrevealed card")]
+    #[test_case(&[(-1, 0), (0, -1)], 0, Some("word0") ; "stops at top left")]
+    #[test_case(&[(0, 9), (9, 0)], 24, Some("word24") ; "stops at bottom right")]
+    #[test_case(&[(4, 0), (1, 0)], 4, Some("word4") ; "does not wrap")]
+    fn cursor(moves: &[(isize, isize)], cursor: usize, selected: Option<&str>) {
+        let mut board = board();
+        for (dx, dy) in moves {
+            board.move_cursor(*dx, *dy);
+        }
+        assert_eq!(cursor, board.cursor);
+        assert_eq!(selected, board.selected());
+    }
+
+    #[test_case(KeyCode::Char('h'), KeyModifiers::NONE, Action::Move(-1, 0))]
+    #[test_case(KeyCode::Down, KeyModifiers::NONE, Action::Move(0, 1))]
+    #[test_case(KeyCode::Enter, KeyModifiers::NONE, Action::Pick)]
+    #[test_case(KeyCode::Char('c'), KeyModifiers::CONTROL, Action::Quit)]
+    #[test_case(KeyCode::Char('c'), KeyModifiers::NONE, Action::Nothing)]
+    fn keys(code: KeyCode, modifiers: KeyModifiers, expected: Action) {
+        assert_eq!(expected, action(KeyEvent { code, modifiers }));
+    }
+}
@@ -0,0 +1,20 @@
+use std::net::IpAddr;
+
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use crate::limit;
+
+/// The client's address, taken from a forwarding header only when the peer is a trusted proxy.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ClientIp {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let forwarded = limit::FORWARDING_HEADERS.iter()
+            .find_map(|name| request.headers().get_one(name).map(|value| (*name, value)));
+        let trusted = &crate::conf::settings().limits.trusted_proxies;
+        Outcome::Success(ClientIp(request.remote().map(|peer| limit::client_address(peer.ip(), forwarded, trusted))))
+    }
+}
@@ -1,7 +1,7 @@
 use rocket::request::{FromRequest, Outcome};
 use rocket::Request;
 
-pub struct WebLanguage(String);
+pub struct WebLanguage(pub String);
 
 impl<'a, 'r> FromRequest<'a, 'r> for WebLanguage {
     type Error = ();
@@ -1,19 +1,65 @@
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use askama::Template;
+use rocket::config::{Config, Environment, Limits};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::request::Form;
+use rocket::response::Redirect;
+use rocket::response::status::{Custom, NotFound};
+use rocket_contrib::json::Json;
 use rocket_contrib::serve::StaticFiles;
+use serde_json::{Map, Value};
 
 use crate::game::{Game, GameWord};
-use crate::game_cache;
+use crate::game::options::GameOptions;
+use crate::game::vote::VotingRule;
+use crate::health;
+use crate::lock_game_cache;
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::res::words::{custom_words, Difficulty, LanguageShare, WordSelection};
+use crate::web::client_ip::ClientIp;
+use crate::web::language::WebLanguage;
 
+pub mod client_ip;
 pub mod language;
+pub mod room;
 pub mod socket;
 
+static NEXT_REQUEST_ID: AtomicUsize = AtomicUsize::new(1);
+
 pub fn start() {
-    let rocket = rocket::Rocket::ignite();
-    let rocket = rocket.mount("/css", StaticFiles::from("static/css"));
-    let rocket = rocket.mount("/js", StaticFiles::from("static/js"));
-    let rocket = rocket.mount("/", routes![favicon, index, game]);
+    let settings = crate::conf::settings();
+    let environment = Environment::active().unwrap_or(Environment::Development);
+    let config = Config::build(environment)
+        .address(settings.http.address.clone())
+        .port(settings.http.port)
+        // room for pasted custom word lists
+        .limits(Limits::new().limit("forms", 64 * 1024))
+        .finalize()
+        .expect("invalid http server configuration");
+    let rocket = rocket::custom(config);
+    let rocket = rocket.attach(AdHoc::on_launch("Health", |_| crate::health::http_launched()));
+    let rocket = rocket.attach(AdHoc::on_request("Log context", |request, _| {
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+        logging::push_context(&[("request", id.to_string()), ("path", request.uri().path().to_string())]);
+    }));
+    let rocket = rocket.attach(AdHoc::on_response("Log context", |request, response| {
+        debug!("{} {} -> {}", request.method(), request.uri(), response.status());
+        logging::pop_context(2);
+    }));
+    let rocket = rocket.attach(AdHoc::on_response("Shutdown", |_, response| {
+        if crate::shutdown::in_progress() {
+            response.set_status(Status::ServiceUnavailable);
+            response.set_sized_body(Cursor::new("The server is shutting down."));
+        }
+    }));
+    let rocket = rocket.mount("/css", StaticFiles::from(&settings.assets.css));
+    let rocket = rocket.mount("/js", StaticFiles::from(&settings.assets.js));
+    let rocket = rocket.mount("/", routes![favicon, index, create_game, game, watch_game, metrics, healthz, readyz]);
     rocket.launch();
 }
 
@@ -24,24 +70,180 @@ fn favicon() {}
 #[template(path = "index.html")]
 struct Index {
     num_running_games: usize,
+    languages: Vec<String>,
+    known_tags: String,
+    selected_language: String,
+    other_languages: String,
+    name: String,
+    include_tags: String,
+    exclude_tags: String,
+    difficulty: String,
+    custom_words: String,
+    custom_percent: String,
+    voting: bool,
+    majority_percent: String,
+    error: String,
+}
+
+impl Index {
+    fn new(language: String) -> Self {
+        let mut languages = crate::res::words::languages();
+        languages.sort();
+        let mut known_tags: Vec<String> = languages.iter()
+            .filter_map(|language| crate::res::words::tags(language).ok())
+            .flat_map(|tags| tags.into_iter().map(|(tag, _)| tag))
+            .collect();
+        known_tags.sort();
+        known_tags.dedup();
+        Self {
+            num_running_games: lock_game_cache().count(),
+            languages,
+            known_tags: known_tags.join(", "),
+            selected_language: language,
+            other_languages: String::new(),
+            name: String::new(),
+            include_tags: String::new(),
+            exclude_tags: String::new(),
+            difficulty: String::new(),
+            custom_words: String::new(),
+            custom_percent: "100".into(),
+            voting: false,
+            majority_percent: "51".into(),
+            error: String::new(),
+        }
+    }
+
+    fn with_error(form: CreateGame, error: String) -> Self {
+        Self {
+            name: form.name,
+            other_languages: form.other_languages,
+            include_tags: form.include_tags,
+            exclude_tags: form.exclude_tags,
+            difficulty: form.difficulty,
+            custom_words: form.custom_words,
+            custom_percent: form.custom_percent,
+            voting: form.voting.is_some(),
+            majority_percent: form.majority_percent,
+            error,
+            ..Index::new(form.language)
+        }
+    }
 }
 
 #[get("/")]
-fn index() -> Index {
-    Index {
-        num_running_games: game_cache().lock().unwrap().count(),
+fn index(language: WebLanguage) -> Index {
+    Index::new(language.0)
+}
+
+#[derive(FromForm)]
+struct CreateGame {
+    name: String,
+    language: String,
+    other_languages: String,
+    include_tags: String,
+    exclude_tags: String,
+    difficulty: String,
+    custom_words: String,
+    custom_percent: String,
+    /// Present when the voting checkbox is ticked.
+    voting: Option<String>,
+    majority_percent: String,
+}
+
+impl CreateGame {
+    fn options(&self) -> Result<GameOptions, String> {
+        let difficulty = match self.difficulty.as_str() {
+            "" | "any" => None,
+            other => Some(Difficulty::parse(other).ok_or_else(|| format!("Unknown difficulty '{}'.", other))?),
+        };
+        let custom_words = custom_words(&self.custom_words).map_err(|e| e.to_string())?;
+        let custom_percent = match self.custom_percent.trim().parse::<u8>() {
+            Ok(percent) if percent <= 100 => percent,
+            _ => return Err(format!("Custom word share must be between 0 and 100, got '{}'.", self.custom_percent)),
+        };
+        let voting = match &self.voting {
+            None => None,
+            Some(_) => match self.majority_percent.trim().parse::<u8>() {
+                Ok(percent) if percent <= 100 => Some(VotingRule { majority_percent: percent }),
+                _ => return Err(format!("Majority must be between 0 and 100, got '{}'.", self.majority_percent)),
+            },
+        };
+        Ok(GameOptions {
+            words: WordSelection {
+                language: self.language.clone(),
+                other_languages: language_shares(&self.other_languages)?,
+                include_tags: tag_list(&self.include_tags),
+                exclude_tags: tag_list(&self.exclude_tags),
+                difficulty,
+                custom_words,
+                custom_percent,
+            },
+            voting,
+        })
+    }
+}
+
+/// Parses `german:40, french:20` into the share of the board each language gets.
+fn language_shares(shares: &str) -> Result<Vec<LanguageShare>, String> {
+    shares.split(',')
+        .map(|share| share.trim())
+        .filter(|share| !share.is_empty())
+        .map(|share| {
+            let mut parts = share.splitn(2, ':');
+            let language = parts.next().unwrap_or("").trim().to_lowercase();
+            match parts.next().map(|percent| percent.trim().parse::<u8>()) {
+                Some(Ok(percent)) if !language.is_empty() && percent <= 100 => Ok(LanguageShare { language, percent }),
+                _ => Err(format!("Expected 'language:percent', got '{}'.", share)),
+            }
+        })
+        .collect()
+}
+
+fn tag_list(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+#[post("/g", data = "<form>")]
+fn create_game(form: Form<CreateGame>, ip: ClientIp) -> Result<Redirect, Custom<Index>> {
+    let form = form.into_inner();
+    let name = form.name.trim().to_string();
+    if let Err(e) = crate::game::name::validate(&name) {
+        return Err(Custom(Status::BadRequest, Index::with_error(form, e.to_string())));
+    }
+    if let Some(ip) = ip.0 {
+        if !crate::limit::allow_game(ip) {
+            METRICS.rate_limited.inc("games");
+            let error = "You created too many games, please wait a minute.".to_string();
+            return Err(Custom(Status::TooManyRequests, Index::with_error(form, error)));
+        }
     }
+    let game = match form.options().and_then(|options| Game::new(name.clone(), options).map_err(|e| e.to_string())) {
+        Ok(game) => game,
+        Err(error) => return Err(Custom(Status::BadRequest, Index::with_error(form, error))),
+    };
+    let created = lock_game_cache().put(game).is_ok();
+    if !created {
+        let error = format!("A game named '{}' already exists, join it instead.", &name);
+        return Err(Custom(Status::BadRequest, Index::with_error(form, error)));
+    }
+    METRICS.games_created.inc();
+    Ok(Redirect::to(uri!(game: name)))
 }
 
 #[derive(Serialize)]
 struct Card {
-    word: String
+    word: String,
+    language: String,
 }
 
 impl From<&GameWord> for Card {
     fn from(w: &GameWord) -> Self {
         Self {
             word: w.word.clone(),
+            language: w.language.clone().unwrap_or_else(|| "custom".into()),
         }
     }
 }
@@ -51,8 +253,16 @@ impl From<&GameWord> for Card {
 struct GamePage {
     game_name: String,
     game_ident: String,
+    /// Key of the spectator link, which does not reveal the game's player page.
+    watch_ident: String,
     socket_url: String,
     cards: Vec<Card>,
+    /// Whether the words come from more than one source, so cards show their language.
+    mixed_languages: bool,
+    /// Read-only view without controls, e.g. for a big screen.
+    spectator: bool,
+    /// Clicking a card votes for it instead of revealing it.
+    voting: bool,
 }
 
 impl From<Arc<Mutex<Game>>> for GamePage {
@@ -61,24 +271,87 @@ impl From<Arc<Mutex<Game>>> for GamePage {
         Self {
             game_name: guard.name.clone(),
             game_ident: guard.ident.clone(),
+            watch_ident: guard.watch_ident.clone(),
             socket_url: socket::socket_url(),
             cards: guard.words.iter().map(|w| w.into()).collect(),
+            mixed_languages: guard.words.iter().any(|w| w.language != guard.words[0].language),
+            spectator: false,
+            voting: guard.options.voting.is_some(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "not_found.html")]
+struct GameNotFoundPage {
+    game_name: String,
+    suggestions: Vec<String>,
+}
+
+impl GameNotFoundPage {
+    fn new(game_name: String, existing: &[String]) -> Self {
+        let suggestions = crate::similarity::similar(&game_name, existing, 3);
+        Self {
+            game_name,
+            suggestions,
         }
     }
 }
 
 #[get("/g/<game_name>")]
-fn game(game_name: String) -> GamePage {
-    let gc = game_cache();
-    let mut cache = gc.lock().unwrap();
-    let g = if let Some(g) = cache.by_name(&game_name) {
-        g
+fn game(game_name: String) -> Result<GamePage, NotFound<GameNotFoundPage>> {
+    let cache = lock_game_cache();
+    if let Some(g) = cache.by_name(&game_name) {
+        Ok(g.into())
     } else {
-        let game = Game::new(game_name.clone(), "english").unwrap();
-        cache.put(game).unwrap();
-        cache.by_name(&game_name).unwrap()
-    };
-    g.into()
+        Err(NotFound(GameNotFoundPage::new(game_name, &cache.names())))
+    }
+}
+
+/// The read-only page behind a spectator link. Unknown links get no suggestions, they would name other games.
+#[get("/w/<watch_ident>")]
+fn watch_game(watch_ident: String) -> Option<GamePage> {
+    let cache = lock_game_cache();
+    let found = cache.names().into_iter()
+        .filter_map(|name| cache.by_name(&name))
+        .find(|g| g.lock().unwrap().matches_watch_ident(&watch_ident))?;
+    let mut page: GamePage = found.into();
+    // the socket server makes everyone presenting the watch ident a spectator
+    page.spectator = true;
+    page.game_ident = watch_ident;
+    Some(page)
+}
+
+#[get("/metrics")]
+fn metrics() -> String {
+    let cached_games = lock_game_cache().count();
+    METRICS.render(cached_games)
+}
+
+fn health_response(report: health::Report) -> Custom<Json<Value>> {
+    let ok = report.is_ok();
+    let mut checks = Map::new();
+    for check in report.checks {
+        let result = match check.result {
+            Ok(()) => "ok".to_string(),
+            Err(problem) => problem,
+        };
+        checks.insert(check.name.into(), Value::String(result));
+    }
+    let mut body = Map::new();
+    body.insert("status".into(), Value::String(if ok { "ok" } else { "failing" }.into()));
+    body.insert("checks".into(), Value::Object(checks));
+    Custom(if ok { Status::Ok } else { Status::ServiceUnavailable }, Json(Value::Object(body)))
+}
+
+#[get("/healthz")]
+fn healthz() -> Custom<Json<Value>> {
+    health_response(health::liveness())
+}
+
+#[get("/readyz")]
+fn readyz() -> Custom<Json<Value>> {
+    health_response(health::readiness(crate::conf::settings().health.cache_lock_timeout))
 }
 
 #[cfg(test)]
@@ -93,20 +366,90 @@ mod tests {
                 word: "horse".into(),
                 team: Team::None,
                 opened: false,
+                language: Some("english".into()),
             };
             let card = Card::from(&word);
             assert_eq!(word.word, card.word);
+            assert_eq!("english", card.language);
+        }
+    }
+
+    mod create_game {
+        use crate::game::vote::VotingRule;
+        use crate::res::words::{Difficulty, LanguageShare};
+        use crate::web::CreateGame;
+
+        #[test]
+        fn options_from_form() {
+            let form = CreateGame {
+                name: "lunch".into(),
+                language: "english".into(),
+                other_languages: "German: 40".into(),
+                include_tags: "Animals, food,".into(),
+                exclude_tags: "".into(),
+                difficulty: "easy".into(),
+                custom_words: "".into(),
+                custom_percent: "100".into(),
+                voting: Some("on".into()),
+                majority_percent: "60".into(),
+            };
+            let options = form.options().unwrap();
+            assert_eq!(vec!["animals".to_string(), "food".into()], options.words.include_tags);
+            assert!(options.words.exclude_tags.is_empty());
+            assert_eq!(Some(Difficulty::Easy), options.words.difficulty);
+            assert!(options.words.custom_words.is_empty());
+            assert_eq!(vec![LanguageShare { language: "german".into(), percent: 40 }], options.words.other_languages);
+            assert_eq!(Some(VotingRule { majority_percent: 60 }), options.voting);
+        }
+
+        #[test_case("" => Ok(vec![]))]
+        #[test_case("german:40, french:20" => Ok(vec![LanguageShare { language: "german".into(), percent: 40 }, LanguageShare { language: "french".into(), percent: 20 }]))]
+        #[test_case("german" => Err("Expected 'language:percent', got 'german'.".to_string()))]
+        #[test_case("german:140" => Err("Expected 'language:percent', got 'german:140'.".to_string()))]
+        fn language_shares(shares: &str) -> Result<Vec<LanguageShare>, String> {
+            crate::web::language_shares(shares)
+        }
+
+        #[test_case("ape\nbook", "50" => Ok((vec!["ape".to_string(), "book".into()], 50)))]
+        #[test_case("ape\nape", "50" => Err("Invalid custom words: \"ape\" appears more than once.".to_string()))]
+        #[test_case("ape", "150" => Err("Custom word share must be between 0 and 100, got '150'.".to_string()))]
+        fn custom_words_from_form(words: &str, percent: &str) -> Result<(Vec<String>, u8), String> {
+            let form = CreateGame {
+                name: "lunch".into(),
+                language: "english".into(),
+                other_languages: "".into(),
+                include_tags: "".into(),
+                exclude_tags: "".into(),
+                difficulty: "any".into(),
+                custom_words: words.into(),
+                custom_percent: percent.into(),
+                voting: None,
+                majority_percent: "".into(),
+            };
+            form.options().map(|options| (options.words.custom_words, options.words.custom_percent))
+        }
+    }
+
+    mod game_not_found_page {
+        use crate::web::GameNotFoundPage;
+
+        #[test]
+        fn suggests_similar_games() {
+            let existing: Vec<String> = vec!["lunchtime".into(), "office".into()];
+            let page = GameNotFoundPage::new("lunchtme".into(), &existing);
+            assert_eq!(vec!["lunchtime".to_string()], page.suggestions);
         }
     }
 
     mod game_page {
         use std::sync::{Arc, Mutex};
         use crate::game::Game;
+        use crate::game::options::GameOptions;
         use crate::web::GamePage;
 
         #[test]
         fn from_game() {
-            let arc = Arc::new(Mutex::new(Game::new("abc".into(), "german").unwrap()));
+            let arc = Arc::new(Mutex::new(Game::new("abc".into(), GameOptions::language("german")).unwrap()));
             let game_page = GamePage::from(arc.clone());
             assert_eq!(game_page.game_name, arc.lock().unwrap().name);
             assert_eq!(game_page.game_ident, arc.lock().unwrap().ident);
@@ -0,0 +1,571 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::export::Formatter;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+use ws::{Message, Sender};
+
+use crate::game::chat::FloodGuard;
+use crate::game::Color;
+pub use crate::protocol::Role;
+use crate::protocol::codec::Encoding;
+
+const MAX_NAME_LENGTH: usize = 24;
+
+lazy_static! {
+    static ref ROOMS: Mutex<Rooms<Client>> = {
+        let settings = &crate::conf::settings().socket;
+        Mutex::new(Rooms::new(settings.replay_events, settings.session_ttl))
+    };
+}
+
+/// A connection and the encoding it asked for.
+#[derive(Clone)]
+struct Client {
+    out: Sender,
+    encoding: Encoding,
+}
+
+/// Where a player sits: their display name, team, whether they give the clues
+/// and whether they confirm guesses for their team in voting mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seat {
+    pub name: String,
+    pub team: Color,
+    pub spymaster: bool,
+    pub captain: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SeatError {
+    EmptyName,
+    NameTooLong,
+    NameTaken(String),
+    CaptainTaken(String),
+    NotAPlayer,
+}
+
+impl Display for SeatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeatError::EmptyName => write!(f, "Please enter a name."),
+            SeatError::NameTooLong => write!(f, "Names can have at most {} characters.", MAX_NAME_LENGTH),
+            SeatError::NameTaken(name) => write!(f, "'{}' is already playing in this game.", name),
+            SeatError::CaptainTaken(name) => write!(f, "{} is already captain of this team.", name),
+            SeatError::NotAPlayer => write!(f, "Spectators cannot join a team."),
+        }
+    }
+}
+
+/// Trims `name` and checks it can be shown in the roster.
+pub fn validate_name(name: &str) -> Result<String, SeatError> {
+    let name: String = name.trim().chars().filter(|c| !c.is_control()).collect();
+    if name.is_empty() {
+        Err(SeatError::EmptyName)
+    } else if name.chars().count() > MAX_NAME_LENGTH {
+        Err(SeatError::NameTooLong)
+    } else {
+        Ok(name)
+    }
+}
+
+/// Who receives an event.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Audience {
+    All,
+    /// Only the seated players of one team, with or without its spymaster.
+    Team { team: Color, spymasters: bool },
+}
+
+impl Audience {
+    fn includes(&self, seat: Option<&Seat>) -> bool {
+        match self {
+            Audience::All => true,
+            Audience::Team { team, spymasters } => seat
+                .map(|seat| seat.team == *team && (*spymasters || !seat.spymaster))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// A broadcast message, kept for a while so reconnecting clients can catch up.
+struct Event {
+    seq: u64,
+    audience: Audience,
+    message: Value,
+}
+
+/// A client's place in a game. Outlives its connection by the session ttl so the client can resume it.
+struct Session {
+    game: String,
+    role: Role,
+    seat: Option<Seat>,
+    /// When the last connection of this session closed, `None` while connected.
+    left: Option<Instant>,
+}
+
+struct Member<T> {
+    out: T,
+    session: String,
+    flood: FloodGuard,
+}
+
+struct Room<T> {
+    members: HashMap<u32, Member<T>>,
+    events: VecDeque<Event>,
+    next_seq: u64,
+}
+
+impl<T> Room<T> {
+    fn new() -> Self {
+        Self {
+            members: HashMap::new(),
+            events: VecDeque::new(),
+            next_seq: 1,
+        }
+    }
+}
+
+/// Socket connections per game, keyed by connection id, and the sessions behind them.
+struct Rooms<T> {
+    games: HashMap<String, Room<T>>,
+    sessions: HashMap<String, Session>,
+    max_events: usize,
+    session_ttl: Duration,
+}
+
+impl<T: Clone> Rooms<T> {
+    fn new(max_events: usize, session_ttl: Duration) -> Self {
+        Self {
+            games: HashMap::new(),
+            sessions: HashMap::new(),
+            max_events,
+            session_ttl,
+        }
+    }
+
+    /// Starts a new session and returns its id.
+    fn join(&mut self, game: &str, connection: u32, out: T, role: Role, now: Instant) -> String {
+        self.expire(now);
+        let session = Uuid::new_v4().to_string();
+        self.sessions.insert(session.clone(), Session {
+            game: game.to_string(),
+            role,
+            seat: None,
+            left: None,
+        });
+        self.attach(game, connection, out, &session);
+        session
+    }
+
+    /// Continues `session` on a new connection, keeping its role and, unless someone took it meanwhile, its seat.
+    /// Returns its role, `None` if there is no such session in `game` with the `role` the client has now.
+    fn resume(&mut self, game: &str, connection: u32, out: T, session: &str, role: Role, now: Instant) -> Option<Role> {
+        self.expire(now);
+        match self.sessions.get(session) {
+            Some(known) if known.game == game && known.role == role => {}
+            _ => return None,
+        }
+        if let Some(room) = self.games.get_mut(game) {
+            room.members.retain(|_, member| member.session != session);
+        }
+        let seat = self.sessions[session].seat.clone();
+        if let Some(seat) = seat {
+            if self.seat_conflict(game, session, &seat).is_some() {
+                self.sessions.get_mut(session).unwrap().seat = None;
+            }
+        }
+        self.sessions.get_mut(session).unwrap().left = None;
+        self.attach(game, connection, out, session);
+        Some(role)
+    }
+
+    fn attach(&mut self, game: &str, connection: u32, out: T, session: &str) {
+        self.games.entry(game.to_string())
+            .or_insert_with(Room::new)
+            .members
+            .insert(connection, Member { out, session: session.to_string(), flood: FloodGuard::default() });
+    }
+
+    fn session(&self, game: &str, connection: u32) -> Option<&String> {
+        self.games.get(game)
+            .and_then(|room| room.members.get(&connection))
+            .map(|member| &member.session)
+    }
+
+    /// Another session of the game holding the seat's name, or its team's captaincy if the seat is a captain.
+    /// Names of disconnected sessions stay reserved until their session expires.
+    fn seat_conflict(&self, game: &str, session: &str, seat: &Seat) -> Option<SeatError> {
+        let others = self.sessions.iter()
+            .filter(|(id, other)| id.as_str() != session && other.game == game)
+            .filter_map(|(_, other)| other.seat.as_ref());
+        for other in others {
+            if other.name == seat.name {
+                return Some(SeatError::NameTaken(seat.name.clone()));
+            }
+            if seat.captain && other.captain && other.team == seat.team {
+                return Some(SeatError::CaptainTaken(other.name.clone()));
+            }
+        }
+        None
+    }
+
+    fn sit(&mut self, game: &str, connection: u32, seat: Seat) -> Result<(), SeatError> {
+        let session = self.session(game, connection).cloned().ok_or(SeatError::NotAPlayer)?;
+        if self.sessions[&session].role != Role::Player {
+            return Err(SeatError::NotAPlayer);
+        }
+        if let Some(conflict) = self.seat_conflict(game, &session, &seat) {
+            return Err(conflict);
+        }
+        self.sessions.get_mut(&session).unwrap().seat = Some(seat);
+        Ok(())
+    }
+
+    fn seat(&self, game: &str, connection: u32) -> Option<Seat> {
+        self.session(game, connection)
+            .and_then(|session| self.sessions.get(session))
+            .and_then(|session| session.seat.clone())
+    }
+
+    fn allow_chat(&mut self, game: &str, connection: u32, now: Instant, max: usize, window: Duration) -> bool {
+        self.games.get_mut(game)
+            .and_then(|room| room.members.get_mut(&connection))
+            .map(|member| member.flood.allow(now, max, window))
+            .unwrap_or(false)
+    }
+
+    fn members(&self, game: &str) -> Vec<(&Member<T>, &Session)> {
+        self.games.get(game)
+            .map(|room| room.members.values()
+                .filter_map(|member| self.sessions.get(&member.session).map(|session| (member, session)))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// Seated players of a game that are currently connected, red team first, spymasters first within a team.
+    fn roster(&self, game: &str) -> Vec<Seat> {
+        let mut seats: Vec<Seat> = self.members(game)
+            .into_iter()
+            .filter_map(|(_, session)| session.seat.clone())
+            .collect();
+        seats.sort_by_key(|seat| (seat.team != Color::Red, !seat.spymaster, seat.name.to_lowercase()));
+        seats
+    }
+
+    fn leave(&mut self, game: &str, connection: u32, now: Instant) {
+        let member = self.games.get_mut(game).and_then(|room| room.members.remove(&connection));
+        if let Some(member) = member {
+            if let Some(session) = self.sessions.get_mut(&member.session) {
+                session.left = Some(now);
+            }
+        }
+        self.expire(now);
+    }
+
+    /// Drops sessions disconnected for longer than the ttl and games nobody can come back to.
+    fn expire(&mut self, now: Instant) {
+        let ttl = self.session_ttl;
+        self.sessions.retain(|_, session| session.left.map(|left| now.duration_since(left) < ttl).unwrap_or(true));
+        let sessions = &self.sessions;
+        self.games.retain(|game, room| !room.members.is_empty() || sessions.values().any(|session| &session.game == game));
+    }
+
+    fn count(&self, game: &str, role: Role) -> usize {
+        self.members(game).into_iter().filter(|(_, session)| session.role == role).count()
+    }
+
+    /// Numbers and stores a message made of `steps` and returns it with the connections in its audience.
+    fn publish(&mut self, game: &str, audience: Audience, steps: Vec<Value>) -> (Value, Vec<T>) {
+        let recipients: Vec<T> = self.members(game)
+            .into_iter()
+            .filter(|(_, session)| audience.includes(session.seat.as_ref()))
+            .map(|(member, _)| member.out.clone())
+            .collect();
+        let max_events = self.max_events;
+        let room = self.games.entry(game.to_string()).or_insert_with(Room::new);
+        let seq = room.next_seq;
+        room.next_seq += 1;
+        let mut message = Map::new();
+        message.insert("game".into(), Value::String(game.to_string()));
+        message.insert("seq".into(), Value::from(seq));
+        message.insert("steps".into(), Value::Array(steps));
+        let message = Value::Object(message);
+        room.events.push_back(Event { seq, audience, message: message.clone() });
+        while room.events.len() > max_events {
+            room.events.pop_front();
+        }
+        (message, recipients)
+    }
+
+    /// Sequence number of the latest event of a game, 0 if there was none.
+    fn latest(&self, game: &str) -> u64 {
+        self.games.get(game).map(|room| room.next_seq - 1).unwrap_or(0)
+    }
+
+    /// The events after `seq` the session may see, `None` if some of them are no longer kept.
+    fn replay(&self, game: &str, session: &str, seq: u64) -> Option<Vec<Value>> {
+        let room = self.games.get(game)?;
+        let seat = self.sessions.get(session)?.seat.as_ref();
+        let oldest = room.events.front().map(|event| event.seq).unwrap_or(room.next_seq);
+        if seq >= room.next_seq || seq + 1 < oldest {
+            return None;
+        }
+        Some(room.events.iter()
+            .filter(|event| event.seq > seq && event.audience.includes(seat))
+            .map(|event| event.message.clone())
+            .collect())
+    }
+}
+
+/// Starts a new session in `game` and returns its id.
+pub fn join(game: &str, out: &Sender, encoding: Encoding, role: Role) -> String {
+    let client = Client { out: out.clone(), encoding };
+    ROOMS.lock().unwrap().join(game, out.connection_id(), client, role, Instant::now())
+}
+
+/// Continues a session of `role` on a new connection, false if the session is unknown, expired or of another role.
+pub fn resume(game: &str, out: &Sender, encoding: Encoding, session: &str, role: Role) -> bool {
+    let client = Client { out: out.clone(), encoding };
+    ROOMS.lock().unwrap().resume(game, out.connection_id(), client, session, role, Instant::now()).is_some()
+}
+
+pub fn leave(game: &str, out: &Sender) {
+    ROOMS.lock().unwrap().leave(game, out.connection_id(), Instant::now());
+}
+
+pub fn sit(game: &str, out: &Sender, seat: Seat) -> Result<(), SeatError> {
+    ROOMS.lock().unwrap().sit(game, out.connection_id(), seat)
+}
+
+pub fn seat(game: &str, out: &Sender) -> Option<Seat> {
+    ROOMS.lock().unwrap().seat(game, out.connection_id())
+}
+
+pub fn allow_chat(game: &str, out: &Sender, max: usize, window: Duration) -> bool {
+    ROOMS.lock().unwrap().allow_chat(game, out.connection_id(), Instant::now(), max, window)
+}
+
+pub fn roster(game: &str) -> Vec<Seat> {
+    ROOMS.lock().unwrap().roster(game)
+}
+
+pub fn spectators(game: &str) -> usize {
+    ROOMS.lock().unwrap().count(game, Role::Spectator)
+}
+
+/// Games with members or sessions that may still resume.
+pub fn games() -> Vec<String> {
+    ROOMS.lock().unwrap().games.keys().cloned().collect()
+}
+
+pub fn latest(game: &str) -> u64 {
+    ROOMS.lock().unwrap().latest(game)
+}
+
+pub fn replay(game: &str, session: &str, seq: u64) -> Option<Vec<Value>> {
+    ROOMS.lock().unwrap().replay(game, session, seq)
+}
+
+/// Sends a numbered event to everyone in its audience and keeps it for replay.
+/// The event is encoded once per encoding in use.
+pub fn publish(game: &str, audience: Audience, steps: Vec<Value>) {
+    let (message, recipients) = ROOMS.lock().unwrap().publish(game, audience, steps);
+    let mut encoded: HashMap<Encoding, Message> = HashMap::new();
+    for client in recipients {
+        let frame = encoded.entry(client.encoding).or_insert_with(|| client.encoding.encode(&message)).clone();
+        if let Err(e) = client.out.send(frame) {
+            debug!("Sending event to connection {} failed: {:?}", client.out.connection_id(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::game::Color;
+    use crate::web::room::{validate_name, Audience, Role, Rooms, Seat, SeatError};
+
+    fn seat(name: &str, team: Color, spymaster: bool) -> Seat {
+        Seat {
+            name: name.into(),
+            team,
+            spymaster,
+            captain: false,
+        }
+    }
+
+    const TTL: Duration = Duration::from_secs(300);
+
+    fn rooms() -> Rooms<&'static str> {
+        Rooms::new(3, TTL)
+    }
+
+    #[test]
+    fn counts_spectators_per_game() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        rooms.join("lunch", 2, "b", Role::Spectator, Instant::now());
+        rooms.join("lunch", 3, "c", Role::Spectator, Instant::now());
+        rooms.join("office", 4, "d", Role::Spectator, Instant::now());
+        assert_eq!(2, rooms.count("lunch", Role::Spectator));
+        rooms.leave("lunch", 3, Instant::now());
+        assert_eq!(1, rooms.count("lunch", Role::Spectator));
+        assert_eq!(1, rooms.count("lunch", Role::Player));
+    }
+
+    #[test]
+    fn empty_games_are_removed() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        let left = Instant::now();
+        rooms.leave("lunch", 1, left);
+        assert!(!rooms.games.is_empty());
+        rooms.expire(left + TTL);
+        assert!(rooms.games.is_empty());
+        assert!(rooms.sessions.is_empty());
+    }
+
+    #[test]
+    fn roster_lists_seated_players() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        rooms.join("lunch", 2, "b", Role::Player, Instant::now());
+        rooms.join("lunch", 3, "c", Role::Player, Instant::now());
+        rooms.join("lunch", 4, "d", Role::Player, Instant::now());
+        rooms.sit("lunch", 1, seat("Zoe", Color::Blue, false)).unwrap();
+        rooms.sit("lunch", 2, seat("Max", Color::Red, false)).unwrap();
+        rooms.sit("lunch", 3, seat("Ann", Color::Red, true)).unwrap();
+        assert_eq!(
+            vec![seat("Ann", Color::Red, true), seat("Max", Color::Red, false), seat("Zoe", Color::Blue, false)],
+            rooms.roster("lunch")
+        );
+        rooms.leave("lunch", 2, Instant::now());
+        assert_eq!(2, rooms.roster("lunch").len());
+    }
+
+    #[test]
+    fn names_are_unique_per_game() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        rooms.join("lunch", 2, "b", Role::Player, Instant::now());
+        rooms.join("lunch", 3, "c", Role::Spectator, Instant::now());
+        rooms.sit("lunch", 1, seat("Ann", Color::Red, false)).unwrap();
+        assert!(rooms.sit("lunch", 1, seat("Ann", Color::Blue, true)).is_ok());
+        assert_eq!(Err(SeatError::NameTaken("Ann".into())), rooms.sit("lunch", 2, seat("Ann", Color::Red, false)));
+        assert_eq!(Err(SeatError::NotAPlayer), rooms.sit("lunch", 3, seat("Bob", Color::Red, false)));
+    }
+
+    #[test]
+    fn one_captain_per_team() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        rooms.join("lunch", 2, "b", Role::Player, Instant::now());
+        rooms.join("lunch", 3, "c", Role::Player, Instant::now());
+        let captain = |name: &str, team: Color| Seat { captain: true, ..seat(name, team, false) };
+        rooms.sit("lunch", 1, captain("Ann", Color::Red)).unwrap();
+        assert_eq!(Err(SeatError::CaptainTaken("Ann".into())), rooms.sit("lunch", 2, captain("Bob", Color::Red)));
+        assert!(rooms.sit("lunch", 3, captain("Eve", Color::Blue)).is_ok());
+    }
+
+    #[test]
+    fn only_members_may_chat() {
+        let mut rooms = rooms();
+        rooms.join("lunch", 1, "a", Role::Player, Instant::now());
+        let window = Duration::from_secs(10);
+        assert!(rooms.allow_chat("lunch", 1, Instant::now(), 1, window));
+        assert!(!rooms.allow_chat("lunch", 1, Instant::now(), 1, window));
+        assert!(!rooms.allow_chat("lunch", 2, Instant::now(), 1, window));
+    }
+
+    #[test]
+    fn resume_keeps_role_and_seat() {
+        let mut rooms = rooms();
+        let now = Instant::now();
+        let session = rooms.join("lunch", 1, "a", Role::Player, now);
+        rooms.sit("lunch", 1, seat("Ann", Color::Red, false)).unwrap();
+        rooms.leave("lunch", 1, now);
+        rooms.join("lunch", 2, "b", Role::Player, now);
+        assert_eq!(Err(SeatError::NameTaken("Ann".into())), rooms.sit("lunch", 2, seat("Ann", Color::Blue, false)));
+        assert_eq!(Some(Role::Player), rooms.resume("lunch", 3, "c", &session, Role::Player, now));
+        assert_eq!(Some(seat("Ann", Color::Red, false)), rooms.seat("lunch", 3));
+        assert_eq!(None, rooms.resume("office", 4, "d", &session, Role::Player, now));
+        assert_eq!(None, rooms.resume("lunch", 4, "d", "unknown", Role::Player, now));
+        assert_eq!(None, rooms.resume("lunch", 4, "d", &session, Role::Spectator, now));
+    }
+
+    #[test]
+    fn expired_sessions_cannot_resume() {
+        let mut rooms = rooms();
+        let now = Instant::now();
+        let session = rooms.join("lunch", 1, "a", Role::Spectator, now);
+        rooms.leave("lunch", 1, now);
+        assert_eq!(None, rooms.resume("lunch", 2, "b", &session, Role::Spectator, now + TTL));
+    }
+
+    #[test]
+    fn resume_replaces_stale_connection() {
+        let mut rooms = rooms();
+        let now = Instant::now();
+        let session = rooms.join("lunch", 1, "a", Role::Player, now);
+        rooms.resume("lunch", 2, "b", &session, Role::Player, now);
+        assert_eq!(1, rooms.count("lunch", Role::Player));
+        let (_, recipients) = rooms.publish("lunch", Audience::All, vec![]);
+        assert_eq!(vec!["b"], recipients);
+    }
+
+    #[test]
+    fn publish_reaches_audience() {
+        let mut rooms = rooms();
+        let now = Instant::now();
+        rooms.join("lunch", 1, "a", Role::Player, now);
+        rooms.join("lunch", 2, "b", Role::Player, now);
+        rooms.join("lunch", 3, "c", Role::Spectator, now);
+        rooms.sit("lunch", 1, seat("Ann", Color::Red, false)).unwrap();
+        rooms.sit("lunch", 2, seat("Bob", Color::Red, true)).unwrap();
+        let team = |spymasters| Audience::Team { team: Color::Red, spymasters };
+        let (_, mut all) = rooms.publish("lunch", Audience::All, vec![]);
+        all.sort();
+        assert_eq!(vec!["a", "b", "c"], all);
+        assert_eq!(vec!["a"], rooms.publish("lunch", team(false), vec![]).1);
+        assert_eq!(2, rooms.publish("lunch", team(true), vec![]).1.len());
+        assert_eq!(3, rooms.latest("lunch"));
+    }
+
+    #[test]
+    fn replay_sends_missed_events() {
+        let mut rooms = rooms();
+        let now = Instant::now();
+        let session = rooms.join("lunch", 1, "a", Role::Spectator, now);
+        let (first, _) = rooms.publish("lunch", Audience::All, vec![]);
+        rooms.publish("lunch", Audience::Team { team: Color::Red, spymasters: true }, vec![]);
+        let (third, _) = rooms.publish("lunch", Audience::All, vec![]);
+        assert_eq!(1, first["seq"]);
+        assert_eq!(Some(vec![third.clone()]), rooms.replay("lunch", &session, 1));
+        assert_eq!(Some(vec![first, third]), rooms.replay("lunch", &session, 0));
+        assert_eq!(Some(vec![]), rooms.replay("lunch", &session, 3));
+        assert_eq!(None, rooms.replay("lunch", &session, 7));
+        rooms.publish("lunch", Audience::All, vec![]);
+        // only the latest three events are kept
+        assert_eq!(None, rooms.replay("lunch", &session, 0));
+        assert_eq!(2, rooms.replay("lunch", &session, 1).unwrap().len());
+    }
+
+    #[test_case(" Ann " => Ok("Ann".to_string()))]
+    #[test_case("  " => Err(SeatError::EmptyName))]
+    #[test_case("a name that is far too long to show" => Err(SeatError::NameTooLong))]
+    fn validate_names(name: &str) -> Result<String, SeatError> {
+        validate_name(name)
+    }
+
+    #[test_case("player" => Some(Role::Player))]
+    #[test_case("spectator" => Some(Role::Spectator))]
+    #[test_case("admin" => None)]
+    fn parse_role(value: &str) -> Option<Role> {
+        Role::parse(value)
+    }
+}
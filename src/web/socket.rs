@@ -1,253 +1,589 @@
 use std::convert::TryFrom;
 
 use serde_json::{Map, Value};
-use ws::{listen, Message};
+use ws::util::{Timeout, Token};
+use ws::{Builder, CloseCode, Frame, Handler, Handshake, Message, Request, Response, Sender};
 
+use crate::game::chat::{self, Channel, ChatError, ChatMessage};
+use crate::game::clue::Clue;
 use crate::game::{Color, Game, RevealOutcome, Team};
-use crate::game_cache;
-#[cfg(debug)]
-use crate::print::ColoredDesc;
+use crate::res::validate::normalize;
+use crate::limit::{self, TokenBucket};
+use crate::lock_game_cache;
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::protocol::codec::Encoding;
+use crate::protocol::{Chat, Join, Msg, MsgParseError, Reset, Reveal, Role, Step, Vote};
+use crate::web::room::{self, Audience, Seat};
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug)]
-enum MsgParseError {
-    BinaryData,
-    Json(serde_json::Error),
-    InvalidJsonStructure,
-    TypeError,
-}
+const PING: Token = Token(1);
 
-impl From<serde_json::Error> for MsgParseError {
-    fn from(e: serde_json::Error) -> MsgParseError {
-        MsgParseError::Json(e)
+impl Step {
+    pub fn execute(&self, game: &str, out: &Sender) -> Option<Value> {
+        match self {
+            Step::Join(j) => join(game, out, j),
+            Step::Chat(c) => chat(game, out, c),
+            Step::Vote(v) => vote(game, out, v),
+            Step::Reveal(r) => reveal(game, r),
+            Step::Reset(r) => reset(game, r),
+            Step::Skip => skip(game),
+            Step::Spy => spy(game, out),
+            Step::Clue(c) => clue(game, out, c),
+        }
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Reveal {
-    pub word: String,
+pub fn socket_url() -> String {
+    crate::conf::settings().socket.url.clone()
 }
 
-#[derive(Debug, PartialEq)]
-enum Step {
-    Reveal(Reveal),
-    Reset(Reset),
-    Skip,
-    Spy,
+/// Tells everyone in a game that the server is going down, in the encoding each client asked for.
+pub fn announce_shutdown() {
+    let mut map = Map::new();
+    map.insert("type".into(), Value::String("shutdown".into()));
+    let step = Value::Object(map);
+    for game in room::games() {
+        room::publish(&game, Audience::All, vec![step.clone()]);
+    }
 }
 
-#[derive(Debug, PartialEq)]
-struct Reset;
+/// Liveness of a connection, renewed by every frame the client sends, pongs included.
+struct Heartbeat {
+    last_seen: Instant,
+    ping: Option<Timeout>,
+}
 
-impl Step {
-    pub fn execute(&self, game: &str) -> Option<Value> {
-        match self {
-            Step::Reveal(r) => reveal(game, r),
-            Step::Reset(r) => reset(game, r),
-            Step::Skip => skip(game),
-            Step::Spy => spy(game),
+impl Heartbeat {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_seen: now,
+            ping: None,
         }
     }
+
+    fn seen(&mut self, now: Instant) {
+        self.last_seen = now;
+    }
+
+    fn alive(&self, now: Instant, idle_timeout: Duration) -> bool {
+        now.duration_since(self.last_seen) < idle_timeout
+    }
 }
 
-#[derive(Debug, PartialEq)]
-struct Msg {
-    pub game: String,
-    pub ident: String,
-    pub steps: Vec<Step>,
+/// Handler with the default behaviour, used to validate frames after noting the activity.
+struct DefaultHandler;
+
+impl Handler for DefaultHandler {}
+
+struct Connection {
+    out: Sender,
+    open: bool,
+    /// The game this connection follows and its role there, set by the first message.
+    room: Option<(String, Role)>,
+    heartbeat: Heartbeat,
+    /// The client's address, `None` if unknown, in which case only the per connection limit applies.
+    ip: Option<IpAddr>,
+    steps: TokenBucket,
+    /// What the client gets sent, negotiated in the handshake.
+    encoding: Encoding,
 }
 
-pub fn socket_url() -> String {
-    let url = crate::conf::websocket_url();
-    println!("socket url: {}", &url);
-    url
+impl Connection {
+    fn send(&self, message: &Value) -> ws::Result<()> {
+        self.out.send(self.encoding.encode(message))
+    }
+
+    /// Leaves the current game and tells the others who is still there.
+    fn leave(&mut self) {
+        if let Some((game, _)) = self.room.take() {
+            room::leave(&game, &self.out);
+            room::publish(&game, Audience::All, vec![Roster::of(&game).into()]);
+        }
+    }
+
+    /// Takes `cost` steps from the connection's and the address' budgets.
+    fn allow_steps(&mut self, cost: u32) -> bool {
+        let rate = &crate::conf::settings().limits.connection_steps;
+        self.steps.allow(rate, Instant::now(), cost) && self.ip.map(|ip| limit::allow_steps(ip, cost)).unwrap_or(true)
+    }
+
+    fn schedule_ping(&self) -> ws::Result<()> {
+        let interval = crate::conf::settings().socket.ping_interval;
+        self.out.timeout(interval.as_millis() as u64, PING)
+    }
+
+    /// Joins the message's game unless already there and returns the role in it, see `role_in`.
+    /// The role cannot change without switching games.
+    /// A known session of the same role is resumed with its seat, and gets the events it missed since `seq`.
+    /// Everyone else gets the chat history they may read.
+    fn enter(&mut self, msg: &Msg) -> ws::Result<Role> {
+        let game = msg.game.as_str();
+        match &self.room {
+            Some((current, current_role)) if current == game => return Ok(*current_role),
+            Some((current, _)) => room::leave(current, &self.out),
+            None => {}
+        }
+        let encoding = self.encoding;
+        let role = match lock_game_cache().by_name(game) {
+            Some(found) => role_in(&found.lock().unwrap(), msg),
+            None => Role::Spectator,
+        };
+        let resumed = msg.session.clone()
+            .filter(|session| room::resume(game, &self.out, encoding, session, role));
+        let session = match &resumed {
+            Some(session) => session.clone(),
+            None => room::join(game, &self.out, encoding, role),
+        };
+        self.room = Some((game.to_string(), role));
+        self.send(&session_message(game, &session, resumed.is_some(), role, &msg.ident))?;
+        let missed = match (&resumed, msg.seq) {
+            (Some(_), Some(seq)) => room::replay(game, &session, seq),
+            _ => None,
+        };
+        match missed {
+            Some(events) => {
+                debug!("Replaying {} events to resumed session", events.len());
+                for event in events {
+                    self.send(&event)?;
+                }
+            }
+            None => self.send(&chat_message(game, chat_history(game, &self.out), true))?,
+        }
+        Ok(role)
+    }
 }
 
-pub fn start() {
-    listen("0.0.0.0:9123", |out| {
-        move |message| {
-            if let Ok(msg) = Msg::try_from(message) {
-                let Msg {
-                    game,
-                    ident,
-                    steps
-                } = msg;
-                let mut response = Map::new();
-                response.insert("game".into(), Value::String(game.clone()));
-                let mut values = vec![];
-                let mut is_ident = false;
-                println!("client ident: {}", &ident);
-                {
-                    let cache = game_cache();
-                    let guard = cache.lock().unwrap();
-                    if let Some(game) = guard.by_name(&game) {
-                        if game.lock().unwrap().matches_ident(&ident) {
-                            is_ident = true;
-                        }
+impl Handler for Connection {
+    fn on_request(&mut self, request: &Request) -> ws::Result<Response> {
+        let mut response = Response::from_request(request)?;
+        self.encoding = Encoding::negotiate(&request.protocols()?);
+        if let Some(protocol) = self.encoding.protocol() {
+            response.set_protocol(protocol);
+        }
+        Ok(response)
+    }
+
+    fn on_open(&mut self, shake: Handshake) -> ws::Result<()> {
+        if crate::shutdown::in_progress() {
+            return self.out.close(CloseCode::Away);
+        }
+        let forwarded = limit::FORWARDING_HEADERS.iter().find_map(|name| {
+            let value = shake.request.header(name)?;
+            std::str::from_utf8(value).ok().map(|value| (*name, value))
+        });
+        let trusted = &crate::conf::settings().limits.trusted_proxies;
+        self.ip = shake.peer_addr.map(|peer| limit::client_address(peer.ip(), forwarded, trusted));
+        if self.ip.map(limit::banned).unwrap_or(false) {
+            debug!("Refusing banned address {:?}", self.ip);
+            return self.out.close(CloseCode::Policy);
+        }
+        self.open = true;
+        METRICS.socket_connections.inc();
+        self.heartbeat.seen(Instant::now());
+        self.schedule_ping()
+    }
+
+    fn on_message(&mut self, message: Message) -> ws::Result<()> {
+        let connection = self.out.connection_id().to_string();
+        logging::with_context(&[("connection", connection)], || {
+            let parsed = if message.len() > crate::conf::settings().limits.max_message_bytes {
+                Err(MsgParseError::TooLarge)
+            } else {
+                Msg::try_from(message)
+            };
+            match parsed {
+                Ok(msg) => {
+                    let game = msg.game.clone();
+                    let ident = msg.ident.clone();
+                    if !self.allow_steps(msg.steps.len().max(1) as u32) {
+                        METRICS.rate_limited.inc("steps");
+                        return self.send(&error_message(&game, "You are sending too fast, slow down."));
+                    }
+                    let role = self.enter(&msg)?;
+                    let out = &self.out;
+                    let (response, changes) = logging::with_context(&[("game", game.clone())], || respond(msg, role, out));
+                    self.send(&response)?;
+                    if changes {
+                        room::publish(&game, Audience::All, state_steps(&game, &ident));
                     }
+                    Ok(())
                 }
-                if is_ident {
-                    for step in steps {
-                        if let Some(result) = step.execute(&game) {
-                            values.push(result);
+                Err(e) => {
+                    debug!("Ignoring invalid message: {:?}", e);
+                    METRICS.socket_parse_errors.inc(e.name());
+                    match self.ip {
+                        Some(ip) if limit::parse_error(ip) => {
+                            warn!("Banning {} for sending invalid messages", ip);
+                            METRICS.bans.inc();
+                            self.out.close(CloseCode::Policy)
                         }
+                        _ => Ok(()),
                     }
                 }
-                if let Some(state) = game_state(&game, &ident) {
-                    values.push(state);
-                }
-                response.insert("steps".into(), Value::Array(values));
-                let text = serde_json::to_string(&response).unwrap();
-                println!("Response: {}", text);
-                out.send(Message::Text(text))
-            } else {
-                Ok(())
             }
-        }
-    }).unwrap();
-}
-
-impl TryFrom<&String> for Msg {
-    type Error = MsgParseError;
-
-    fn try_from(value: &String) -> Result<Self, Self::Error> {
-        println!("client message: {}", value);
-        let parsed: Value = serde_json::from_str(&value)?;
-        match parsed {
-            Value::Object(obj) => {
-                let game = game_name(&obj)?;
-                let ident = ident(&obj)?;
-                let steps = steps(&obj)?;
-                Ok(Self {
-                    game,
-                    ident,
-                    steps,
-                })
+        })
+    }
+
+    fn on_close(&mut self, _code: CloseCode, _reason: &str) {
+        if let Some(ping) = self.heartbeat.ping.take() {
+            if let Err(e) = self.out.cancel(ping) {
+                debug!("Cancelling ping failed: {:?}", e);
             }
-            _ => Err(MsgParseError::InvalidJsonStructure),
+        }
+        self.leave();
+        if self.open {
+            self.open = false;
+            METRICS.socket_connections.dec();
         }
     }
-}
 
-impl TryFrom<Message> for Msg {
-    type Error = MsgParseError;
+    fn on_timeout(&mut self, event: Token) -> ws::Result<()> {
+        if event != PING {
+            return Ok(());
+        }
+        self.heartbeat.ping = None;
+        if self.heartbeat.alive(Instant::now(), crate::conf::settings().socket.idle_timeout) {
+            self.out.ping(vec![])?;
+            self.schedule_ping()
+        } else {
+            // a dead peer may never answer the close handshake, so presence is updated right away
+            debug!("Connection {} timed out", self.out.connection_id());
+            METRICS.socket_timeouts.inc();
+            self.leave();
+            self.out.close(CloseCode::Away)
+        }
+    }
 
-    fn try_from(value: Message) -> Result<Self, Self::Error> {
-        match value {
-            Message::Text(text) => Msg::try_from(&text),
-            Message::Binary(_) => Err(MsgParseError::BinaryData),
+    fn on_new_timeout(&mut self, event: Token, timeout: Timeout) -> ws::Result<()> {
+        if event == PING {
+            if let Some(old) = self.heartbeat.ping.replace(timeout) {
+                self.out.cancel(old)?;
+            }
         }
+        Ok(())
+    }
+
+    fn on_frame(&mut self, frame: Frame) -> ws::Result<Option<Frame>> {
+        self.heartbeat.seen(Instant::now());
+        DefaultHandler.on_frame(frame)
     }
 }
 
-impl TryFrom<&Map<String, Value>> for Step {
-    type Error = MsgParseError;
+pub fn start() {
+    let settings = &crate::conf::settings().socket;
+    let limits = &crate::conf::settings().limits;
+    let socket = Builder::new()
+        .with_settings(ws::Settings {
+            max_fragment_size: limits.max_message_bytes,
+            ..ws::Settings::default()
+        })
+        .build(move |out: Sender| Connection {
+            out,
+            open: false,
+            room: None,
+            heartbeat: Heartbeat::new(Instant::now()),
+            ip: None,
+            steps: TokenBucket::new(&limits.connection_steps, Instant::now()),
+            encoding: Encoding::Json,
+        }).unwrap();
+    let socket = socket.bind((settings.address.as_str(), settings.port)).unwrap();
+    let _listening = crate::health::SocketListening::new();
+    crate::shutdown::register_socket(socket.broadcaster());
+    socket.run().unwrap();
+    crate::shutdown::socket_stopped();
+}
+
+/// The role a message gets in `game`. Only the game's ident makes a player, and only if the client does not ask to watch.
+/// Everyone else is a spectator.
+fn role_in(game: &Game, msg: &Msg) -> Role {
+    if msg.role == Role::Player && game.matches_ident(&msg.ident) {
+        Role::Player
+    } else {
+        Role::Spectator
+    }
+}
 
-    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
-        match value.get("type") {
-            Some(Value::String(step_type)) => {
-                match step_type.as_str() {
-                    "reveal" => Ok(Step::Reveal(Reveal::try_from(value)?)),
-                    "reset" => Ok(Step::Reset(Reset::try_from(value)?)),
-                    "skip" => Ok(Step::Skip),
-                    "spy" => Ok(Step::Spy),
-                    _ => Err(MsgParseError::TypeError),
-                }
+/// Runs the steps of a player presenting the current ident and answers with the state.
+/// Also returns whether steps ran, so the change can be pushed to the others.
+fn respond(msg: Msg, role: Role, out: &Sender) -> (Value, bool) {
+    let Msg {
+        game,
+        ident,
+        steps,
+        ..
+    } = msg;
+    let mut values = vec![];
+    let mut is_ident = false;
+    {
+        let guard = lock_game_cache();
+        if let Some(game) = guard.by_name(&game) {
+            if game.lock().unwrap().matches_ident(&ident) {
+                is_ident = true;
+            }
+        }
+    }
+    let executed = role == Role::Player && is_ident && !steps.is_empty();
+    if role == Role::Spectator && !steps.is_empty() {
+        debug!("Ignoring {} steps from spectator", steps.len());
+    } else if is_ident {
+        for step in steps {
+            METRICS.socket_steps.inc(step.name());
+            if let Some(result) = step.execute(&game, out) {
+                values.push(result);
             }
-            _ => Err(MsgParseError::InvalidJsonStructure),
         }
     }
+    values.push(game_state(&game, &ident, role));
+    values.push(Roster::of(&game).into());
+    let response = message(&game, values);
+    trace!("Response: {}", response);
+    (response, executed)
+}
+
+fn message(game: &str, steps: Vec<Value>) -> Value {
+    let mut message = Map::new();
+    message.insert("game".into(), Value::String(game.to_string()));
+    message.insert("steps".into(), Value::Array(steps));
+    Value::Object(message)
 }
 
-impl TryFrom<&Map<String, Value>> for Reveal {
-    type Error = MsgParseError;
+/// The current state of a game as pushed to everyone watching it, a bare reload once the game was replaced.
+fn state_steps(game: &str, ident: &str) -> Vec<Value> {
+    vec![game_state(game, ident, Role::Player), Roster::of(game).into()]
+}
 
-    fn try_from(value: &Map<String, Value>) -> Result<Self, Self::Error> {
-        if let Some(Value::String(word)) = value.get("word") {
-            Ok(Reveal {
-                word: word.clone(),
-            })
-        } else {
-            Err(MsgParseError::InvalidJsonStructure)
+/// Tells the client which session to resume after reconnecting and the event it is up to date with.
+/// The game's ident is only confirmed to a client that presented it.
+fn session_message(game: &str, session: &str, resumed: bool, role: Role, ident: &str) -> Value {
+    let mut map = Map::new();
+    map.insert("type".into(), Value::String("session".into()));
+    map.insert("id".into(), Value::String(session.to_string()));
+    map.insert("resumed".into(), Value::Bool(resumed));
+    map.insert("seq".into(), Value::from(room::latest(game)));
+    if let Some(found) = lock_game_cache().by_name(game) {
+        if role_ident(&found.lock().unwrap(), role) == ident {
+            map.insert("ident".into(), Value::String(ident.to_string()));
         }
     }
+    message(game, vec![Value::Object(map)])
 }
 
-impl TryFrom<&Map<String, Value>> for Reset {
-    type Error = MsgParseError;
 
-    fn try_from(_value: &Map<String, Value>) -> Result<Self, Self::Error> {
-        Ok(Reset)
+fn error(message: String) -> Value {
+    let mut map = Map::new();
+    map.insert("type".into(), Value::String("error".into()));
+    map.insert("message".into(), Value::String(message));
+    Value::Object(map)
+}
+
+fn join(g: &str, out: &Sender, j: &Join) -> Option<Value> {
+    let seat = room::validate_name(&j.name).and_then(|name| {
+        room::sit(g, out, Seat {
+            name,
+            team: j.team.clone(),
+            spymaster: j.spymaster,
+            captain: j.captain,
+        })
+    });
+    match seat {
+        Ok(()) => Some(ChatLog { messages: chat_history(g, out), history: true }.into()),
+        Err(e) => Some(error(e.to_string())),
     }
 }
 
-fn game_name(obj: &Map<String, Value>) -> Result<String, MsgParseError> {
-    if let Some(Value::String(name)) = obj.get("game") {
-        Ok(name.clone())
-    } else {
-        Err(MsgParseError::InvalidJsonStructure)
+fn chat(g: &str, out: &Sender, c: &Chat) -> Option<Value> {
+    match post(g, out, c) {
+        Ok(()) => None,
+        Err(e) => Some(error(e.to_string())),
     }
 }
 
-fn ident(obj: &Map<String, Value>) -> Result<String, MsgParseError> {
-    if let Some(Value::String(name)) = obj.get("ident") {
-        Ok(name.clone())
-    } else {
-        Err(MsgParseError::InvalidJsonStructure)
+/// Records the message with the game and sends it to everyone allowed to read it.
+fn post(g: &str, out: &Sender, c: &Chat) -> Result<(), ChatError> {
+    let settings = &crate::conf::settings().chat;
+    let text = chat::validate_text(&c.text, settings.max_length)?;
+    let seat = room::seat(g, out).ok_or(ChatError::NotSeated)?;
+    if c.channel == Channel::Team && seat.spymaster && !settings.spymasters_read_team {
+        return Err(ChatError::SpymasterInTeamChannel);
+    }
+    if !room::allow_chat(g, out, settings.max_messages, settings.window) {
+        return Err(ChatError::Flooding);
+    }
+    let message = ChatMessage {
+        name: seat.name,
+        team: seat.team,
+        spymaster: seat.spymaster,
+        channel: c.channel,
+        text,
+        sent: SystemTime::now(),
+    };
+    if let Some(game) = lock_game_cache().by_name(g) {
+        chat::record(&mut game.lock().unwrap().chat, message.clone(), settings.history);
     }
+    let audience = match message.channel {
+        Channel::Global => Audience::All,
+        Channel::Team => Audience::Team {
+            team: message.team.clone(),
+            spymasters: settings.spymasters_read_team,
+        },
+    };
+    room::publish(g, audience, vec![ChatLog { messages: vec![message], history: false }.into()]);
+    Ok(())
+}
+
+/// The stored messages of a game that the connection may read.
+fn chat_history(g: &str, out: &Sender) -> Vec<ChatMessage> {
+    let read = crate::conf::settings().chat.spymasters_read_team;
+    let seat = room::seat(g, out);
+    let reader = seat.as_ref().map(|seat| (&seat.team, seat.spymaster));
+    match lock_game_cache().by_name(g) {
+        Some(game) => game.lock().unwrap().chat.iter()
+            .filter(|message| message.visible_to(reader, read))
+            .cloned()
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn error_message(game: &str, text: &str) -> Value {
+    message(game, vec![error(text.to_string())])
 }
 
-fn steps(obj: &Map<String, Value>) -> Result<Vec<Step>, MsgParseError> {
-    if let Some(Value::Array(values)) = obj.get("steps") {
-        let mut steps: Vec<Step> = vec![];
-        for value in values {
-            if let Value::Object(map) = value {
-                steps.push(Step::try_from(map)?);
+fn chat_message(game: &str, messages: Vec<ChatMessage>, history: bool) -> Value {
+    message(game, vec![ChatLog { messages, history }.into()])
+}
+
+fn reset(g: &str, _r: &Reset) -> Option<Value> {
+    let mut lock = lock_game_cache();
+    let next = lock.by_name(g).map(|game| game.lock().unwrap().next());
+    if lock.delete(g).is_err() {
+        warn!("error deleting game {}", g);
+    }
+    let mut ident = None;
+    match next {
+        Some(Ok(game)) => {
+            let next_ident = game.ident.clone();
+            if lock.put(game).is_ok() {
+                METRICS.games_created.inc();
+                ident = Some(next_ident);
             } else {
-                return Err(MsgParseError::InvalidJsonStructure);
+                error!("error recreating game {}", g);
             }
         }
-        Ok(steps)
-    } else {
-        Err(MsgParseError::InvalidJsonStructure)
+        Some(Err(e)) => error!("error recreating game {}: {:?}", g, e),
+        None => {}
     }
+    Some(reload(ident))
 }
 
-fn reset(g: &str, _r: &Reset) -> Option<Value> {
-    let cache = game_cache();
-    let mut lock = cache.lock().unwrap();
-    if lock.delete(g).is_err() {
-        eprintln!("error deleting game {}", g);
-    }
+/// Tells the client its view is outdated, with the ident of the game it should show now if there is one.
+fn reload(ident: Option<String>) -> Value {
     let mut map = Map::new();
     map.insert("type".into(), Value::String("reload".into()));
-    Some(Value::Object(map))
+    if let Some(ident) = ident {
+        map.insert("ident".into(), Value::String(ident));
+    }
+    Value::Object(map)
 }
 
 fn reveal(g: &str, r: &Reveal) -> Option<Value> {
     with_game_name_do(g, |game| {
         let mut game_lock = game.lock().unwrap();
-        let outcome = game_lock.reveal(&r.word);
-        if outcome.eq(&RevealOutcome::Nop) {
-            None
+        if game_lock.options.voting.is_some() {
+            return Some(error("This game uses voting, vote for a card instead.".into()));
+        }
+        open(&mut game_lock, &r.word)
+    })
+}
+
+fn open(game: &mut Game, word: &str) -> Option<Value> {
+    let outcome = game.reveal(word);
+    if outcome.eq(&RevealOutcome::Nop) {
+        None
+    } else {
+        debug!("Reveal outcome: {:?}", outcome);
+        if let RevealOutcome::Opened(_, team) = &outcome {
+            let team: Value = team.clone().into();
+            METRICS.reveal_outcomes.inc(team.as_str().unwrap_or_default());
+        }
+        if game.winner.is_some() {
+            METRICS.games_finished.inc();
+        }
+        Some(outcome.into())
+    }
+}
+
+/// Names of the seated operatives of `team`, the players who vote in voting mode.
+fn operatives(g: &str, team: &Color) -> Vec<String> {
+    room::roster(g)
+        .into_iter()
+        .filter(|seat| seat.team == *team && !seat.spymaster)
+        .map(|seat| seat.name)
+        .collect()
+}
+
+/// Records a vote and reveals the card once enough operatives agree or the captain votes for it.
+fn vote(g: &str, out: &Sender, v: &Vote) -> Option<Value> {
+    let seat = match room::seat(g, out) {
+        Some(seat) => seat,
+        None => return Some(error("Join a team to vote.".into())),
+    };
+    with_game_name_do(g, |game| {
+        let mut game_lock = game.lock().unwrap();
+        let rule = match game_lock.options.voting.clone() {
+            Some(rule) => rule,
+            None => return Some(error("This game does not use voting.".into())),
+        };
+        if seat.spymaster || seat.team != game_lock.turn || game_lock.winner.is_some() {
+            return Some(error("Only operatives of the team whose turn it is can vote.".into()));
+        }
+        let word = normalize(&v.word);
+        if !game_lock.words.iter().any(|w| w.word == word && !w.opened) {
+            return None;
+        }
+        game_lock.ballot.vote(&seat.name, &word);
+        let decided = if seat.captain {
+            Some(word)
         } else {
-            println!("Reveal outcome: {:?}", outcome);
-            Some(outcome.into())
+            game_lock.ballot.decided(&operatives(g, &seat.team), &rule)
+        };
+        decided.and_then(|word| open(&mut game_lock, &word))
+    })
+}
+
+fn clue(g: &str, out: &Sender, c: &Clue) -> Option<Value> {
+    let seat = match room::seat(g, out) {
+        Some(seat) => seat,
+        None => return Some(error("Join a team to give clues.".into())),
+    };
+    with_game_name_do(g, |game| {
+        let mut game_lock = game.lock().unwrap();
+        if !seat.spymaster || seat.team != game_lock.turn {
+            return Some(error("Only the spymaster of the team whose turn it is can give a clue.".into()));
+        }
+        match game_lock.give_clue(c.clone()) {
+            Ok(()) => None,
+            Err(e) => Some(error(e.to_string())),
         }
     })
 }
 
 fn skip(g: &str) -> Option<Value> {
     with_game_name_do(g, |game| {
-        let mut game_lock = game.lock().unwrap();
-        game_lock.turn = game_lock.turn.invert();
+        game.lock().unwrap().skip();
         None
     })
 }
 
-fn spy(g: &str) -> Option<Value> {
-    let cache = game_cache();
-    let lock = cache.lock().unwrap();
+fn spy(g: &str, out: &Sender) -> Option<Value> {
+    match room::seat(g, out) {
+        Some(ref seat) if seat.spymaster => {}
+        _ => return Some(error("Only spymasters can see the key card.".into())),
+    }
+    let lock = lock_game_cache();
     if let Some(game) = lock.by_name(&g) {
         let game: Game = game.lock().unwrap().clone();
         let spy_data = SpyData::from(&game);
@@ -257,8 +593,7 @@ fn spy(g: &str) -> Option<Value> {
 }
 
 fn with_game_name_do<T>(g: &str, f: T) -> Option<Value> where T: Fn(Arc<Mutex<Game>>) -> Option<Value> {
-    let cache = game_cache();
-    let lock = cache.lock().unwrap();
+    let lock = lock_game_cache();
     if let Some(game) = lock.by_name(&g) {
         return f(game)
     }
@@ -266,20 +601,31 @@ fn with_game_name_do<T>(g: &str, f: T) -> Option<Value> where T: Fn(Arc<Mutex<Ga
 }
 
 struct GameState {
+    pub words: Vec<String>,
     pub current_team: Color,
     pub winner: Option<Color>,
     pub revealed: Vec<RevealOutcome>,
+    pub spectators: usize,
+    /// Proposed cards with their voters and the votes a card needs, in voting mode.
+    pub votes: Option<(Vec<(String, Vec<String>)>, usize)>,
+    pub clue: Option<Clue>,
+    pub guesses_left: Option<usize>,
 }
 
 impl From<Game> for GameState {
     fn from(game: Game) -> Self {
         Self {
+            words: game.words.iter().map(|gw| gw.word.clone()).collect(),
             current_team: game.turn.clone(),
             winner: game.winner.clone(),
             revealed: game.words.iter()
                 .filter(|gw| gw.opened)
                 .map(|gw| RevealOutcome::Opened(gw.word.clone(), gw.team.clone()))
                 .collect(),
+            spectators: 0,
+            votes: None,
+            clue: game.clue.clone(),
+            guesses_left: game.guesses_left(),
         }
     }
 }
@@ -288,6 +634,7 @@ impl Into<Value> for GameState {
     fn into(self) -> Value {
         let mut map = Map::new();
         map.insert("type".into(), Value::String("state".into()));
+        map.insert("words".into(), Value::Array(self.words.into_iter().map(Value::String).collect()));
         map.insert("team".into(), Value::String(self.current_team.to_string()));
         if let Some(winner) = self.winner {
             map.insert("winner".into(), Value::String(winner.to_string()));
@@ -296,6 +643,28 @@ impl Into<Value> for GameState {
             .map(|outcome| outcome.into())
             .collect()
         ));
+        map.insert("spectators".into(), Value::from(self.spectators));
+        if let Some((votes, needed)) = self.votes {
+            map.insert("votes".into(), Value::Array(votes.into_iter()
+                .map(|(word, voters)| {
+                    let mut map = Map::new();
+                    map.insert("word".into(), Value::String(word));
+                    map.insert("voters".into(), Value::Array(voters.into_iter().map(Value::String).collect()));
+                    Value::Object(map)
+                })
+                .collect()
+            ));
+            map.insert("votes_needed".into(), Value::from(needed));
+        }
+        if let Some(clue) = self.clue {
+            let mut map_clue = Map::new();
+            map_clue.insert("word".into(), Value::String(clue.word));
+            map_clue.insert("count".into(), Value::from(clue.count));
+            map.insert("clue".into(), Value::Object(map_clue));
+        }
+        if let Some(left) = self.guesses_left {
+            map.insert("guesses_left".into(), Value::from(left));
+        }
         Value::Object(map)
     }
 }
@@ -310,6 +679,70 @@ impl Into<Value> for Team {
     }
 }
 
+/// Chat messages for a client. `history` replaces what the client shows instead of adding to it.
+struct ChatLog {
+    pub messages: Vec<ChatMessage>,
+    pub history: bool,
+}
+
+impl Into<Value> for ChatLog {
+    fn into(self) -> Value {
+        let mut map = Map::new();
+        map.insert("type".into(), Value::String("chat".into()));
+        map.insert("history".into(), Value::Bool(self.history));
+        map.insert("messages".into(), Value::Array(
+            self.messages
+                .into_iter()
+                .map(|message| {
+                    let sent = message.sent.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                    let mut map = Map::new();
+                    map.insert("name".into(), Value::String(message.name));
+                    map.insert("team".into(), Value::String(message.team.to_string()));
+                    map.insert("spymaster".into(), Value::Bool(message.spymaster));
+                    map.insert("channel".into(), Value::String(message.channel.to_string()));
+                    map.insert("text".into(), Value::String(message.text));
+                    map.insert("sent".into(), Value::from(sent));
+                    Value::Object(map)
+                })
+                .collect())
+        );
+        Value::Object(map)
+    }
+}
+
+struct Roster {
+    pub seats: Vec<Seat>,
+}
+
+impl Roster {
+    fn of(game: &str) -> Self {
+        Self {
+            seats: room::roster(game),
+        }
+    }
+}
+
+impl Into<Value> for Roster {
+    fn into(self) -> Value {
+        let mut map = Map::new();
+        map.insert("type".into(), Value::String("roster".into()));
+        map.insert("players".into(), Value::Array(
+            self.seats
+                .into_iter()
+                .map(|seat| {
+                    let mut map = Map::new();
+                    map.insert("name".into(), Value::String(seat.name));
+                    map.insert("team".into(), Value::String(seat.team.to_string()));
+                    map.insert("spymaster".into(), Value::Bool(seat.spymaster));
+                    map.insert("captain".into(), Value::Bool(seat.captain));
+                    Value::Object(map)
+                })
+                .collect())
+        );
+        Value::Object(map)
+    }
+}
+
 struct SpyData {
     pub cards: Vec<(String, Team)>,
 }
@@ -344,23 +777,32 @@ impl Into<Value> for SpyData {
     }
 }
 
-fn game_state(g: &str, i: &str) -> Option<Value> {
-    if let Some(v) = with_game_name_do(g, |game| {
-        let game: Game = game.lock().unwrap().clone();
-        if game.ident.eq(i) {
-            #[cfg(debug)]{
-                println!("{}", game.desc_colored());
-            }
-            let state = GameState::from(game);
-            return Some(state.into());
-        }
-        None
-    }) {
-        return Some(v);
+/// The ident clients of `role` use for `game`.
+fn role_ident(game: &Game, role: Role) -> &str {
+    match role {
+        Role::Player => &game.ident,
+        Role::Spectator => &game.watch_ident,
     }
-    let mut map = Map::new();
-    map.insert("type".into(), Value::String("reload".into()));
-    Some(Value::Object(map))
+}
+
+/// The state of the game if `i` is its current ident for `role`, otherwise a reload that does not tell the ident.
+fn game_state(g: &str, i: &str, role: Role) -> Value {
+    let found = match lock_game_cache().by_name(g) {
+        Some(found) => found,
+        None => return reload(None),
+    };
+    let game: Game = found.lock().unwrap().clone();
+    if role_ident(&game, role) != i {
+        return reload(None);
+    }
+    let votes = game.options.voting.as_ref().map(|rule| {
+        let operatives = operatives(g, &game.turn);
+        (game.ballot.tally(&operatives).into_iter().collect(), rule.needed(operatives.len()))
+    });
+    let mut state = GameState::from(game);
+    state.spectators = room::spectators(g);
+    state.votes = votes;
+    state.into()
 }
 
 impl Into<Value> for RevealOutcome {
@@ -406,63 +848,83 @@ impl Into<Value> for Win {
 #[cfg(test)]
 mod tests {
     use std::convert::TryFrom;
-
-    use serde_json::{Map, Value};
-
-    use crate::game::{Color, RevealOutcome, Team};
-    use crate::web::socket::{Msg, Reveal, Step, Turn, Win};
+    use std::time::{Duration, Instant};
+
+    use serde_json::Value;
+
+    use crate::game::clue::Clue;
+    use crate::game::options::GameOptions;
+    use crate::game::{Color, Game, RevealOutcome, Team};
+    use crate::protocol::{Card, Event, Msg, Role, ServerMsg, State, Votes};
+    use crate::web::room::Seat;
+    use crate::web::socket::{message, role_in, GameState, Heartbeat, Roster, Turn, Win};
+
+    #[test_case("player", Role::Player => Role::Player ; "player ident")]
+    #[test_case("player", Role::Spectator => Role::Spectator ; "player ident asking to watch")]
+    #[test_case("watch", Role::Player => Role::Spectator ; "watch ident claiming to play")]
+    #[test_case("", Role::Player => Role::Spectator ; "no ident")]
+    #[test_case("stale", Role::Player => Role::Spectator ; "wrong ident")]
+    fn role_is_decided_by_ident(ident: &str, requested: Role) -> Role {
+        let mut game = Game::new("lunch".into(), GameOptions::language("english")).unwrap();
+        game.ident = "player".into();
+        game.watch_ident = "watch".into();
+        let msg = Msg {
+            game: "lunch".into(),
+            ident: ident.into(),
+            role: requested,
+            session: None,
+            seq: None,
+            steps: vec![],
+        };
+        role_in(&game, &msg)
+    }
 
     #[test]
-    fn msg_from_string() {
-        let expected = Msg {
-            game: "Abc".into(),
-            ident: "ABC123".to_string(),
-            steps: vec![
-                Step::Reveal(Reveal {
-                    word: "show".into(),
-                }),
-            ],
-        };
-        let actual = Msg::try_from(
-            &r#"{
-            "game": "Abc",
-            "ident": "ABC123",
-            "steps": [
-                {
-                    "type": "reveal",
-                    "word": "show"
-                }
-            ]
-            }"#.to_string()).unwrap();
-        assert_eq!(expected, actual);
+    fn heartbeat_expires_without_frames() {
+        let start = Instant::now();
+        let idle_timeout = Duration::from_secs(45);
+        let mut heartbeat = Heartbeat::new(start);
+        assert!(heartbeat.alive(start + Duration::from_secs(44), idle_timeout));
+        assert!(!heartbeat.alive(start + idle_timeout, idle_timeout));
+        heartbeat.seen(start + Duration::from_secs(30));
+        assert!(heartbeat.alive(start + idle_timeout, idle_timeout));
     }
 
     #[test]
-    fn reveal_from_map() {
-        let expected = Reveal {
-            word: "house".into(),
+    fn state_decodes_as_event() {
+        let state = GameState {
+            words: vec!["boat".into(), "house".into()],
+            current_team: Color::Blue,
+            winner: None,
+            revealed: vec![RevealOutcome::Opened("boat".into(), Team::None)],
+            spectators: 2,
+            votes: Some((vec![("house".into(), vec!["Ann".into()])], 2)),
+            clue: Some(Clue { word: "ocean".into(), count: 2 }),
+            guesses_left: Some(3),
         };
-        let mut map = Map::new();
-        map.insert("type".into(), Value::String("reveal".into()));
-        map.insert("word".into(), Value::String("house".into()));
-        let actual = Reveal::try_from(
-            &map
-        ).unwrap();
-        assert_eq!(expected, actual);
+        let expected = Event::State(State {
+            words: vec!["boat".into(), "house".into()],
+            team: Color::Blue,
+            winner: None,
+            revealed: vec![Card { word: "boat".into(), team: Team::None }],
+            spectators: 2,
+            votes: Some(Votes { proposals: vec![("house".into(), vec!["Ann".into()])], needed: 2 }),
+            clue: Some(Clue { word: "ocean".into(), count: 2 }),
+            guesses_left: Some(3),
+        });
+        let decoded = ServerMsg::try_from(message("Abc", vec![state.into()])).unwrap();
+        assert_eq!(vec![expected], decoded.events);
     }
 
     #[test]
-    fn step_from_map() {
-        let expected = Step::Reveal(Reveal {
-            word: "house".into(),
-        });
-        let mut map = Map::new();
-        map.insert("type".into(), Value::String("reveal".into()));
-        map.insert("word".into(), Value::String("house".into()));
-        let actual = Step::try_from(
-            &map
+    fn roster_to_value() {
+        let value: Value = serde_json::from_str(
+            r#"{"type": "roster", "players": [{"name": "Ann", "team": "red", "spymaster": false, "captain": true}]}"#
         ).unwrap();
-        assert_eq!(expected, actual);
+        let actual: Value = Roster {
+            seats: vec![Seat { name: "Ann".into(), team: Color::Red, spymaster: false, captain: true }],
+        }.into();
+        assert_eq!(value, actual);
     }
 
     #[test]
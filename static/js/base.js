@@ -1,7 +1,7 @@
 var connection = undefined;
 
 let wsConnection = function wsConnection(url) {
-    if (connection === undefined) {
+    if (connection === undefined || connection.readyState >= WebSocket.CLOSING) {
         connection = new WebSocket(url);
     }
     return connection;